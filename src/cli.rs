@@ -0,0 +1,272 @@
+use std::path::PathBuf;
+use std::thread;
+
+use clap::builder::BoolishValueParser;
+use clap::{Args, Parser, Subcommand};
+use open_library_extractor::store::MIN_MAP_SIZE;
+use open_library_extractor::DocumentType;
+
+use crate::output::{Compression, Format, Sharding};
+
+/// Extracts the books, works and authors of an Open Library dump into ndJSON documents.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Reads the dump and exports the books, authors and works in a single pass.
+    Extract {
+        /// The dump to read, e.g. `ol_dump_latest.txt.gz`.
+        dump: PathBuf,
+
+        #[command(flatten)]
+        read: ReadArgs,
+
+        #[command(flatten)]
+        store: StoreArgs,
+
+        #[command(flatten)]
+        documents: DocumentArgs,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Reads the dump into a persistent index that can be exported many times.
+    Index {
+        /// The dump to read, e.g. `ol_dump_latest.txt.gz`.
+        dump: PathBuf,
+
+        /// The directory of the index, it must not contain an index already.
+        #[arg(long)]
+        index_dir: PathBuf,
+
+        /// The initial size of the index, e.g. `20G`, at least `1M`, it grows automatically when full.
+        #[arg(long, default_value = "10G", value_parser = parse_map_size)]
+        map_size: usize,
+
+        #[command(flatten)]
+        read: ReadArgs,
+    },
+    /// Exports the books, authors and works of an index built by the `index` subcommand.
+    Export {
+        /// The directory of the index.
+        #[arg(long)]
+        index_dir: PathBuf,
+
+        /// The number of worker threads, all the available cores by default.
+        #[arg(long, default_value_t = available_threads())]
+        threads: usize,
+
+        #[command(flatten)]
+        documents: DocumentArgs,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Counts the records of the dump by type.
+    Stats {
+        /// The dump to read, e.g. `ol_dump_latest.txt.gz`.
+        dump: PathBuf,
+    },
+    /// Checks that every record of the dump can be read and parsed.
+    Validate {
+        /// The dump to read, e.g. `ol_dump_latest.txt.gz`.
+        dump: PathBuf,
+
+        #[command(flatten)]
+        read: ReadArgs,
+    },
+    /// Prints the documents of the given keys from an index, following the redirects.
+    Lookup {
+        /// The keys to look up, e.g. `/books/OL10000135M`, `/authors/OL1A` or `/languages/eng`.
+        #[arg(required = true)]
+        keys: Vec<String>,
+
+        /// The directory of the index.
+        #[arg(long)]
+        index_dir: PathBuf,
+
+        #[command(flatten)]
+        documents: DocumentArgs,
+    },
+}
+
+/// How the records of the dump are read.
+#[derive(Debug, Args)]
+pub struct ReadArgs {
+    /// The number of worker threads, all the available cores by default.
+    #[arg(long, default_value_t = available_threads())]
+    pub threads: usize,
+
+    /// Writes the records that can't be read or parsed to this file, with their line number and the error.
+    #[arg(long)]
+    pub rejects: Option<PathBuf>,
+
+    /// Stops at the first record that can't be read or parsed instead of skipping it.
+    #[arg(long)]
+    pub strict: bool,
+}
+
+/// Where the authors, works and redirects are kept while reading the dump.
+#[derive(Debug, Args)]
+pub struct StoreArgs {
+    /// The directory in which the temporary index is created, the system temporary directory by default.
+    #[arg(long, conflicts_with = "in_memory")]
+    pub index_dir: Option<PathBuf>,
+
+    /// The initial size of the temporary index, e.g. `20G`, at least `1M`, it grows automatically when full.
+    #[arg(long, default_value = "10G", value_parser = parse_map_size)]
+    pub map_size: usize,
+
+    /// Keeps the authors, works and redirects in memory instead of a temporary index.
+    #[arg(long)]
+    pub in_memory: bool,
+}
+
+/// What the exported documents contain.
+#[derive(Debug, Default, Args)]
+pub struct DocumentArgs {
+    /// The identifier schemes to export (e.g. `goodreads,oclc_numbers`), all of them by default.
+    #[arg(long, env = "OL_IDENTIFIERS", value_delimiter = ',')]
+    pub identifiers: Vec<String>,
+
+    /// Exports the authors as `{ "id": ..., "name": ... }` objects instead of names.
+    #[arg(long, env = "OL_NESTED_AUTHORS", value_parser = BoolishValueParser::new())]
+    pub nested_authors: bool,
+
+    /// Makes the editions without authors inherit the authors of their work.
+    #[arg(long, env = "OL_WORK_AUTHORS_FALLBACK", value_parser = BoolishValueParser::new())]
+    pub work_authors_fallback: bool,
+
+    /// The types of documents to export (`book`, `author` or `work`), all of them by default.
+    #[arg(long, value_delimiter = ',')]
+    pub types: Vec<DocumentType>,
+
+    /// The fields of the documents to export (e.g. `name,authors,isbns`), all of them by default.
+    /// The `type` and `id` fields are always exported, unless `--no-type-tag` is given.
+    #[arg(long, value_delimiter = ',')]
+    pub fields: Vec<String>,
+}
+
+/// Where the documents are written.
+#[derive(Debug, Args)]
+pub struct OutputArgs {
+    /// The file in which the documents are written, the standard output by default.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Writes each type of documents into its own file of this directory,
+    /// i.e. `books.ndjson`, `authors.ndjson` and `works.ndjson`.
+    #[arg(long, conflicts_with = "output")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Removes the `type` field of the documents, only when each file holds one type,
+    /// i.e. with `--output-dir` or a single type with `--types`.
+    #[arg(long)]
+    pub no_type_tag: bool,
+
+    /// The format of the documents, the Parquet, Arrow and CSV files only hold a single type of documents.
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+
+    /// The compression of the output, guessed from the extension of the output file by default.
+    #[arg(long, value_enum)]
+    pub compression: Option<Compression>,
+
+    /// Splits the documents into this number of files, e.g. `books-0001.ndjson`, `books-0002.ndjson`...
+    #[arg(long, conflicts_with = "max_shard_size", value_parser = clap::value_parser!(u32).range(1..))]
+    pub shards: Option<u32>,
+
+    /// Splits the documents into files of at most this size, e.g. `100M`, before compression.
+    #[arg(long, value_parser = parse_size)]
+    pub max_shard_size: Option<usize>,
+
+    /// The columns of the CSV file of the books (e.g. `id,name,authors,goodreads`), the identifier schemes can be used.
+    #[arg(long, value_delimiter = ',')]
+    pub book_columns: Vec<String>,
+
+    /// The columns of the CSV file of the authors (e.g. `id,name,wikidata`), the identifier schemes can be used.
+    #[arg(long, value_delimiter = ',')]
+    pub author_columns: Vec<String>,
+
+    /// The separator of the values of the lists in the CSV files, e.g. the `authors` or the `subjects`.
+    #[arg(long, default_value = "|")]
+    pub array_separator: String,
+
+    /// Writes a CSV row for each value of this list (e.g. `authors`) instead of joining them, the other lists stay joined.
+    #[arg(long, value_name = "COLUMN")]
+    pub explode: Option<String>,
+
+    /// Sends the documents to this Meilisearch instance, e.g. `http://localhost:7700`, instead of writing them.
+    #[arg(long, env = "MEILISEARCH_URL", conflicts_with_all = ["output", "output_dir"])]
+    pub meilisearch_url: Option<String>,
+
+    /// The Meilisearch index in which the documents are sent, with `id` as its primary key.
+    #[arg(long, default_value = "books")]
+    pub meilisearch_index: String,
+
+    /// The API key of Meilisearch, e.g. the master key.
+    #[arg(long, env = "MEILISEARCH_API_KEY", hide_env_values = true)]
+    pub meilisearch_api_key: Option<String>,
+
+    /// The maximum size of the payloads of documents sent to Meilisearch.
+    #[arg(long, default_value = "32M", value_parser = parse_size)]
+    pub max_payload_size: usize,
+
+    /// Writes the settings of a Meilisearch index matching the exported fields to this file, e.g. `settings.json`.
+    #[arg(long)]
+    pub meilisearch_settings: Option<PathBuf>,
+
+    /// Adds the alternate names of the authors as synonyms to the settings of the Meilisearch index,
+    /// leaving out the names shared by several authors.
+    #[arg(long, requires = "meilisearch_settings")]
+    pub author_synonyms: bool,
+
+    /// The maximum number of names of authors in the synonyms.
+    #[arg(long, default_value_t = 100_000, requires = "author_synonyms")]
+    pub max_author_synonyms: usize,
+}
+
+impl OutputArgs {
+    pub fn sharding(&self) -> Option<Sharding> {
+        match (self.shards, self.max_shard_size) {
+            (Some(count), _) => Some(Sharding::Count(count as usize)),
+            (None, Some(size)) => Some(Sharding::MaxSize(size)),
+            (None, None) => None,
+        }
+    }
+}
+
+fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Parses a size like `10GiB`, `512M` or `1048576`.
+fn parse_size(text: &str) -> Result<usize, String> {
+    let text = text.trim();
+    let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(digits);
+    let number: usize = number.parse().map_err(|e| format!("invalid size {:?}: {}", text, e))?;
+    let multiplier: usize = match unit.trim().to_ascii_lowercase().trim_end_matches("ib").trim_end_matches('b') {
+        "" => 1,
+        "k" => 1024,
+        "m" => 1024 * 1024,
+        "g" => 1024 * 1024 * 1024,
+        "t" => 1024 * 1024 * 1024 * 1024,
+        _ => return Err(format!("invalid size unit {:?}", unit)),
+    };
+    number.checked_mul(multiplier).ok_or_else(|| format!("size too big {:?}", text))
+}
+
+/// Parses the size of an LMDB map, which can't be smaller than the minimum of the store.
+fn parse_map_size(text: &str) -> Result<usize, String> {
+    let size = parse_size(text)?;
+    if size < MIN_MAP_SIZE {
+        return Err(format!("the map size must be at least {} bytes", MIN_MAP_SIZE));
+    }
+    Ok(size)
+}
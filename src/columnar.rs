@@ -0,0 +1,474 @@
+//! Writes the documents as typed columns, into Parquet or Arrow IPC files.
+//!
+//! Each type of documents has its own schema, so a [`ColumnarSink`] only writes one type,
+//! use a [`ByTypeSink`](crate::ByTypeSink) to write each type into its own file.
+
+use std::convert::TryFrom;
+use std::io;
+use std::sync::Arc;
+
+use arrow_array::builder::{ArrayBuilder, Int32Builder, Int64Builder, ListBuilder, MapBuilder, StringBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Fields, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+
+use crate::extract::DocumentType;
+use crate::object::{variant_name, OutAuthor, OutObject};
+use crate::sink::{Encoder, Sink};
+
+/// The format of the columnar files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnarFormat {
+    /// Parquet files, compressed with zstd.
+    Parquet,
+    /// Arrow IPC files, also known as Feather files.
+    ArrowIpc,
+}
+
+impl ColumnarFormat {
+    /// The extension of the files of this format, e.g. `parquet`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ColumnarFormat::Parquet => "parquet",
+            ColumnarFormat::ArrowIpc => "arrow",
+        }
+    }
+}
+
+/// The schema of the columns of a type of documents.
+///
+/// The lists of names (e.g. `authors`, `subjects`, `isbns`) are lists of strings, the years
+/// and the number of pages are integers and the identifiers are maps of lists of strings.
+/// The `author_ids` are only filled when the authors are nested, in the same order as the `authors`.
+pub fn schema(kind: DocumentType) -> SchemaRef {
+    let text = |name: &str| Field::new(name, DataType::Utf8, true);
+    let list = |name: &str, item: DataType| Field::new(name, DataType::List(Arc::new(Field::new("item", item, true))), true);
+    let map = |name: &str, value: DataType| {
+        let entries = Fields::from(vec![Field::new("keys", DataType::Utf8, false), Field::new("values", value, true)]);
+        Field::new(name, DataType::Map(Arc::new(Field::new("entries", DataType::Struct(entries), false)), false), true)
+    };
+    let id = Field::new("id", DataType::Utf8, false);
+
+    let fields = match kind {
+        DocumentType::Book => vec![
+            id,
+            text("name"),
+            text("subtitle"),
+            text("work_id"),
+            list("authors", DataType::Utf8),
+            list("author_ids", DataType::Utf8),
+            Field::new("publish_year", DataType::Int32, true),
+            text("publish_date"),
+            text("publish_date_precision"),
+            Field::new("number_of_pages", DataType::Int64, true),
+            list("publishers", DataType::Utf8),
+            text("format"),
+            text("physical_format"),
+            list("subjects", DataType::Utf8),
+            list("languages", DataType::Utf8),
+            list("isbns", DataType::Utf8),
+            map("identifiers", DataType::List(Arc::new(Field::new("item", DataType::Utf8, true)))),
+        ],
+        DocumentType::Author => vec![
+            id,
+            text("name"),
+            text("personal_name"),
+            list("alternate_names", DataType::Utf8),
+            text("birth_date"),
+            Field::new("birth_year", DataType::Int32, true),
+            text("death_date"),
+            Field::new("death_year", DataType::Int32, true),
+            text("bio"),
+            list("photos", DataType::Int64),
+            map("remote_ids", DataType::Utf8),
+        ],
+        DocumentType::Work => vec![
+            id,
+            text("name"),
+            list("authors", DataType::Utf8),
+            list("author_ids", DataType::Utf8),
+            list("subjects", DataType::Utf8),
+            text("description"),
+            text("first_publish_date"),
+        ],
+    };
+
+    Arc::new(Schema::new(fields))
+}
+
+type TextList = ListBuilder<StringBuilder>;
+
+/// A map of strings to values, that can be derived as `Default` unlike a `MapBuilder`.
+struct TextMap<V: ArrayBuilder>(MapBuilder<StringBuilder, V>);
+
+impl<V: ArrayBuilder + Default> Default for TextMap<V> {
+    fn default() -> TextMap<V> {
+        TextMap(MapBuilder::new(None, StringBuilder::new(), V::default()))
+    }
+}
+
+#[derive(Default)]
+struct BookColumns {
+    id: StringBuilder,
+    name: StringBuilder,
+    subtitle: StringBuilder,
+    work_id: StringBuilder,
+    authors: TextList,
+    author_ids: TextList,
+    publish_year: Int32Builder,
+    publish_date: StringBuilder,
+    publish_date_precision: StringBuilder,
+    number_of_pages: Int64Builder,
+    publishers: TextList,
+    format: StringBuilder,
+    physical_format: StringBuilder,
+    subjects: TextList,
+    languages: TextList,
+    isbns: TextList,
+    identifiers: TextMap<TextList>,
+}
+
+#[derive(Default)]
+struct AuthorColumns {
+    id: StringBuilder,
+    name: StringBuilder,
+    personal_name: StringBuilder,
+    alternate_names: TextList,
+    birth_date: StringBuilder,
+    birth_year: Int32Builder,
+    death_date: StringBuilder,
+    death_year: Int32Builder,
+    bio: StringBuilder,
+    photos: ListBuilder<Int64Builder>,
+    remote_ids: TextMap<StringBuilder>,
+}
+
+#[derive(Default)]
+struct WorkColumns {
+    id: StringBuilder,
+    name: StringBuilder,
+    authors: TextList,
+    author_ids: TextList,
+    subjects: TextList,
+    description: StringBuilder,
+    first_publish_date: StringBuilder,
+}
+
+/// The columns of a batch of documents of a single type, being built.
+enum Columns {
+    Book(Box<BookColumns>),
+    Author(Box<AuthorColumns>),
+    Work(Box<WorkColumns>),
+}
+
+impl Columns {
+    fn new(kind: DocumentType) -> Columns {
+        match kind {
+            DocumentType::Book => Columns::Book(Box::default()),
+            DocumentType::Author => Columns::Author(Box::default()),
+            DocumentType::Work => Columns::Work(Box::default()),
+        }
+    }
+
+    fn kind(&self) -> DocumentType {
+        match self {
+            Columns::Book(_) => DocumentType::Book,
+            Columns::Author(_) => DocumentType::Author,
+            Columns::Work(_) => DocumentType::Work,
+        }
+    }
+
+    fn push(&mut self, object: OutObject) -> anyhow::Result<()> {
+        match (self, object) {
+            (Columns::Book(c), OutObject::Book {
+                id, name, subtitle, work_id, authors, publish_year, publish_date, publish_date_precision,
+                number_of_pages, publishers, format, physical_format, subjects, languages, isbns, identifiers,
+            }) => {
+                c.id.append_value(id);
+                c.name.append_value(name);
+                c.subtitle.append_option(subtitle);
+                c.work_id.append_option(work_id);
+                append_authors(&mut c.authors, &mut c.author_ids, &authors);
+                c.publish_year.append_option(publish_year.map(i32::try_from).transpose()?);
+                c.publish_date.append_option(publish_date);
+                c.publish_date_precision.append_option(publish_date_precision.map(variant_name).transpose()?);
+                c.number_of_pages.append_option(number_of_pages.map(i64::try_from).transpose()?);
+                c.publishers.append_value(publishers.into_iter().map(Some));
+                c.format.append_option(format.map(variant_name).transpose()?);
+                c.physical_format.append_option(physical_format);
+                c.subjects.append_value(subjects.into_iter().map(Some));
+                c.languages.append_value(languages.into_iter().map(Some));
+                c.isbns.append_value(isbns.into_iter().map(Some));
+                for (scheme, values) in identifiers {
+                    c.identifiers.0.keys().append_value(scheme);
+                    c.identifiers.0.values().append_value(values.into_iter().map(Some));
+                }
+                c.identifiers.0.append(true)?;
+            },
+            (Columns::Author(c), OutObject::Author {
+                id, name, personal_name, alternate_names, birth_date, birth_year,
+                death_date, death_year, bio, photos, remote_ids,
+            }) => {
+                c.id.append_value(id);
+                c.name.append_value(name);
+                c.personal_name.append_option(personal_name);
+                c.alternate_names.append_value(alternate_names.into_iter().map(Some));
+                c.birth_date.append_option(birth_date);
+                c.birth_year.append_option(birth_year.map(i32::try_from).transpose()?);
+                c.death_date.append_option(death_date);
+                c.death_year.append_option(death_year.map(i32::try_from).transpose()?);
+                c.bio.append_option(bio);
+                c.photos.append_value(photos.into_iter().map(Some));
+                for (source, remote_id) in remote_ids {
+                    c.remote_ids.0.keys().append_value(source);
+                    c.remote_ids.0.values().append_value(remote_id);
+                }
+                c.remote_ids.0.append(true)?;
+            },
+            (Columns::Work(c), OutObject::Work { id, name, authors, subjects, description, first_publish_date }) => {
+                c.id.append_value(id);
+                c.name.append_value(name);
+                append_authors(&mut c.authors, &mut c.author_ids, &authors);
+                c.subjects.append_value(subjects.into_iter().map(Some));
+                c.description.append_option(description);
+                c.first_publish_date.append_option(first_publish_date);
+            },
+            (columns, object) => anyhow::bail!(
+                "a {} can't be written with the {}s",
+                object.document_type().name(),
+                columns.kind().name(),
+            ),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> anyhow::Result<RecordBatch> {
+        let kind = self.kind();
+        let columns: Vec<ArrayRef> = match self {
+            Columns::Book(mut c) => vec![
+                Arc::new(c.id.finish()),
+                Arc::new(c.name.finish()),
+                Arc::new(c.subtitle.finish()),
+                Arc::new(c.work_id.finish()),
+                Arc::new(c.authors.finish()),
+                Arc::new(c.author_ids.finish()),
+                Arc::new(c.publish_year.finish()),
+                Arc::new(c.publish_date.finish()),
+                Arc::new(c.publish_date_precision.finish()),
+                Arc::new(c.number_of_pages.finish()),
+                Arc::new(c.publishers.finish()),
+                Arc::new(c.format.finish()),
+                Arc::new(c.physical_format.finish()),
+                Arc::new(c.subjects.finish()),
+                Arc::new(c.languages.finish()),
+                Arc::new(c.isbns.finish()),
+                Arc::new(c.identifiers.0.finish()),
+            ],
+            Columns::Author(mut c) => vec![
+                Arc::new(c.id.finish()),
+                Arc::new(c.name.finish()),
+                Arc::new(c.personal_name.finish()),
+                Arc::new(c.alternate_names.finish()),
+                Arc::new(c.birth_date.finish()),
+                Arc::new(c.birth_year.finish()),
+                Arc::new(c.death_date.finish()),
+                Arc::new(c.death_year.finish()),
+                Arc::new(c.bio.finish()),
+                Arc::new(c.photos.finish()),
+                Arc::new(c.remote_ids.0.finish()),
+            ],
+            Columns::Work(mut c) => vec![
+                Arc::new(c.id.finish()),
+                Arc::new(c.name.finish()),
+                Arc::new(c.authors.finish()),
+                Arc::new(c.author_ids.finish()),
+                Arc::new(c.subjects.finish()),
+                Arc::new(c.description.finish()),
+                Arc::new(c.first_publish_date.finish()),
+            ],
+        };
+        RecordBatch::try_new(schema(kind), columns).map_err(Into::into)
+    }
+}
+
+/// Appends the names of the authors, and their ids if they are nested.
+fn append_authors(names: &mut TextList, ids: &mut TextList, authors: &[OutAuthor]) {
+    names.append_value(authors.iter().map(|author| match author {
+        OutAuthor::Name(name) | OutAuthor::Object { name, .. } => Some(name),
+    }));
+    if authors.iter().all(|author| matches!(author, OutAuthor::Object { .. })) {
+        ids.append_value(authors.iter().map(|author| match author {
+            OutAuthor::Name(_) => None,
+            OutAuthor::Object { id, .. } => Some(id),
+        }));
+    } else {
+        ids.append_null();
+    }
+}
+
+/// Builds the columns of the documents, on the worker threads.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ColumnarEncoder;
+
+/// The columns of a batch of documents, empty until the first document.
+#[derive(Default)]
+pub struct ColumnsBatch(Option<Columns>);
+
+impl Encoder for ColumnarEncoder {
+    type Batch = ColumnsBatch;
+
+    fn encode(&self, batch: &mut ColumnsBatch, object: OutObject) -> anyhow::Result<()> {
+        batch.0.get_or_insert_with(|| Columns::new(object.document_type())).push(object)
+    }
+}
+
+enum Writer<W: io::Write + Send> {
+    Parquet(ArrowWriter<W>),
+    ArrowIpc(arrow_ipc::writer::FileWriter<W>),
+    /// The file is complete, the writer is given back.
+    Finished(W),
+}
+
+/// Writes one type of documents as a Parquet or an Arrow IPC file.
+pub struct ColumnarSink<W: io::Write + Send> {
+    kind: DocumentType,
+    /// Only `None` while the file is being finished.
+    writer: Option<Writer<W>>,
+}
+
+impl<W: io::Write + Send> ColumnarSink<W> {
+    pub fn new(writer: W, kind: DocumentType, format: ColumnarFormat) -> anyhow::Result<ColumnarSink<W>> {
+        let writer = match format {
+            ColumnarFormat::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                    .build();
+                Writer::Parquet(ArrowWriter::try_new(writer, schema(kind), Some(properties))?)
+            },
+            ColumnarFormat::ArrowIpc => {
+                Writer::ArrowIpc(arrow_ipc::writer::FileWriter::try_new(writer, &schema(kind))?)
+            },
+        };
+        Ok(ColumnarSink { kind, writer: Some(writer) })
+    }
+
+    /// Returns the writer, completing the file if the sink isn't finished.
+    pub fn into_inner(mut self) -> anyhow::Result<W> {
+        match self.writer.take() {
+            Some(Writer::Parquet(writer)) => writer.into_inner().map_err(Into::into),
+            Some(Writer::ArrowIpc(writer)) => writer.into_inner().map_err(Into::into),
+            Some(Writer::Finished(writer)) => Ok(writer),
+            None => anyhow::bail!("the file couldn't be finished"),
+        }
+    }
+}
+
+impl<W: io::Write + Send> Sink for ColumnarSink<W> {
+    type Encoder = ColumnarEncoder;
+
+    fn encoder(&self) -> ColumnarEncoder {
+        ColumnarEncoder
+    }
+
+    fn write(&mut self, batch: ColumnsBatch) -> anyhow::Result<()> {
+        let columns = match batch.0 {
+            Some(columns) => columns,
+            None => return Ok(()),
+        };
+        if columns.kind() != self.kind {
+            anyhow::bail!("{}s can't be written to the file of the {}s", columns.kind().name(), self.kind.name());
+        }
+
+        let batch = columns.finish()?;
+        match &mut self.writer {
+            Some(Writer::Parquet(writer)) => writer.write(&batch)?,
+            Some(Writer::ArrowIpc(writer)) => writer.write(&batch)?,
+            Some(Writer::Finished(_)) | None => anyhow::bail!("the file of the {}s is already finished", self.kind.name()),
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        let writer = match self.writer.take() {
+            Some(Writer::Parquet(writer)) => writer.into_inner()?,
+            Some(Writer::ArrowIpc(writer)) => writer.into_inner()?,
+            Some(Writer::Finished(writer)) => writer,
+            None => anyhow::bail!("the file couldn't be finished"),
+        };
+        self.writer = Some(Writer::Finished(writer));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Seek, SeekFrom};
+
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Int32Type, Int64Type};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::*;
+    use crate::extract::Options;
+    use crate::sink::ByTypeSink;
+    use crate::testing::extract_sample;
+
+    #[test]
+    fn write_typed_parquet_columns() {
+        let options = Options { nested_authors: true, ..Options::default() };
+        let sinks = DocumentType::ALL.iter()
+            .map(|kind| (*kind, ColumnarSink::new(tempfile::tempfile().unwrap(), *kind, ColumnarFormat::Parquet).unwrap()))
+            .collect();
+        let mut sink = ByTypeSink::new(sinks);
+        extract_sample(options, &mut sink).unwrap();
+
+        let mut files = sink.into_inner().into_iter().map(|(kind, sink)| (kind, sink.into_inner().unwrap()));
+        let (kind, mut file) = files.next().unwrap();
+        assert_eq!(kind, DocumentType::Book);
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap().build().unwrap();
+        let batches: Vec<_> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 100);
+        assert_eq!(batches[0].schema(), schema(DocumentType::Book));
+
+        let book = batches.iter().find_map(|batch| {
+            let ids = batch.column_by_name("id").unwrap().as_string::<i32>();
+            ids.iter().position(|id| id == Some("OL10000135M")).map(|row| batch.slice(row, 1))
+        }).unwrap();
+        let column = |name| book.column_by_name(name).unwrap();
+        assert_eq!(column("publish_year").as_primitive::<Int32Type>().value(0), 1993);
+        assert_eq!(column("work_id").as_string::<i32>().value(0), "OL7925046W");
+        assert_eq!(column("format").as_string::<i32>().value(0), "hardcover");
+        let isbns = column("isbns").as_list::<i32>().value(0);
+        assert_eq!(isbns.as_string::<i32>().value(0), "9780107805401");
+        assert_eq!(column("number_of_pages").as_primitive::<Int64Type>().value(0), 64);
+
+        let (kind, mut file) = files.next().unwrap();
+        assert_eq!(kind, DocumentType::Author);
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap().build().unwrap();
+        assert_eq!(reader.map(|batch| batch.unwrap().num_rows()).sum::<usize>(), 97);
+    }
+
+    #[test]
+    fn write_an_arrow_ipc_file() {
+        let options = Options { types: vec![DocumentType::Author], ..Options::default() };
+        let mut sink = ColumnarSink::new(Vec::new(), DocumentType::Author, ColumnarFormat::ArrowIpc).unwrap();
+        extract_sample(options, &mut sink).unwrap();
+
+        let file = io::Cursor::new(sink.into_inner().unwrap());
+        let reader = arrow_ipc::reader::FileReader::try_new(file, None).unwrap();
+        assert_eq!(reader.schema(), schema(DocumentType::Author));
+        let names: Vec<_> = reader
+            .flat_map(|batch| {
+                let batch = batch.unwrap();
+                let names = batch.column_by_name("name").unwrap().as_string::<i32>().clone();
+                names.iter().map(|name| name.unwrap().to_owned()).collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(names.len(), 97);
+        assert!(names.iter().any(|name| name == "Harald A. Enge"));
+    }
+}
@@ -0,0 +1,284 @@
+use serde::Serialize;
+
+/// How precise a parsed publish date is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    Day,
+    Month,
+    Year,
+    Decade,
+    Century,
+}
+
+/// A publish date extracted from the free-text `publish_date` field of an edition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublishDate {
+    pub year: u32,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+    pub precision: Precision,
+}
+
+impl PublishDate {
+    fn year(year: u32) -> PublishDate {
+        PublishDate { year, month: None, day: None, precision: Precision::Year }
+    }
+
+    fn month(year: u32, month: u32) -> PublishDate {
+        PublishDate { year, month: Some(month), day: None, precision: Precision::Month }
+    }
+
+    fn day(year: u32, month: u32, day: u32) -> Option<PublishDate> {
+        if day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(PublishDate { year, month: Some(month), day: Some(day), precision: Precision::Day })
+    }
+
+    /// Returns the ISO 8601 representation of this date (e.g. `2001`, `2001-03`, `2001-03-05`),
+    /// decades and centuries have no ISO representation.
+    pub fn to_iso(self) -> Option<String> {
+        match (self.precision, self.month, self.day) {
+            (Precision::Day, Some(month), Some(day)) => Some(format!("{:04}-{:02}-{:02}", self.year, month, day)),
+            (Precision::Month, Some(month), _) => Some(format!("{:04}-{:02}", self.year, month)),
+            (Precision::Year, _, _) => Some(format!("{:04}", self.year)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Number,
+    Word,
+    /// A truncated year like `199-` or `1990s`, the year is the first of the period.
+    Period(u32, Precision),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: Kind,
+    text: &'a str,
+    end: usize,
+}
+
+/// Parses the first date found in an Open Library free-text date,
+/// e.g. `"March 5, 2001 (reprint 2004)"`, `"c1990"`, `"1987?"` or `"19--"`.
+pub fn parse(text: &str) -> Option<PublishDate> {
+    let tokens = tokenize(text);
+
+    let (i, token) = tokens.iter().enumerate().find(|(_, t)| match t.kind {
+        Kind::Number => t.text.len() == 4 && !t.text.starts_with('0'),
+        Kind::Period(..) => true,
+        Kind::Word => false,
+    })?;
+
+    let year = match token.kind {
+        Kind::Period(year, precision) => {
+            return Some(PublishDate { year, month: None, day: None, precision });
+        },
+        _ => token.text.parse().ok()?,
+    };
+
+    // ISO-like dates, e.g. `1999-07-02`, `1999/07` or `1999.07.02`.
+    if let Some(date) = parse_iso_suffix(year, &text[token.end..]) {
+        return Some(date);
+    }
+
+    let previous = |n: usize| i.checked_sub(n).map(|j| tokens[j]);
+    let date = match (previous(2), previous(1)) {
+        // `March 5, 2001`
+        (Some(m), Some(d)) if is_word(m) && is_day(d) => match month_number(m.text) {
+            Some(month) => PublishDate::day(year, month, d.text.parse().ok()?),
+            None => None,
+        },
+        // `5 March 2001`
+        (Some(d), Some(m)) if is_day(d) && is_word(m) => match month_number(m.text) {
+            Some(month) => PublishDate::day(year, month, d.text.parse().ok()?),
+            None => None,
+        },
+        // `12/31/1992` or `31/12/1992`, ambiguous dates only keep the year
+        (Some(a), Some(b)) if is_day(a) && is_day(b) => {
+            let (a, b): (u32, u32) = (a.text.parse().ok()?, b.text.parse().ok()?);
+            match (a, b) {
+                (a, b) if a > 12 && b <= 12 => PublishDate::day(year, b, a),
+                (a, b) if b > 12 && a <= 12 => PublishDate::day(year, a, b),
+                _ => None,
+            }
+        },
+        _ => None,
+    };
+
+    if let Some(date) = date {
+        return Some(date);
+    }
+
+    // `December 1993`
+    match previous(1) {
+        Some(m) if is_word(m) => match month_number(m.text) {
+            Some(month) => Some(PublishDate::month(year, month)),
+            None => Some(PublishDate::year(year)),
+        },
+        _ => Some(PublishDate::year(year)),
+    }
+}
+
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut start = 0;
+
+    while start < bytes.len() {
+        let b = bytes[start];
+        if b.is_ascii_digit() {
+            let end = start + bytes[start..].iter().take_while(|b| b.is_ascii_digit()).count();
+            let digits = &text[start..end];
+
+            // Unknown trailing digits, e.g. `19--`, `199-`, `199?` or `19uu`.
+            let unknown = bytes[end..].iter()
+                .take_while(|b| matches!(b, b'-' | b'?' | b'u' | b'x' | b'_'))
+                .take(4 - digits.len().min(4))
+                .count();
+
+            let kind = match (digits.len(), unknown) {
+                (3, 1) => Kind::Period(digits.parse::<u32>().unwrap() * 10, Precision::Decade),
+                (2, 2) => Kind::Period(digits.parse::<u32>().unwrap() * 100, Precision::Century),
+                // `1990s` or `1990's`
+                (4, _) if digits.ends_with('0') && is_decade_suffix(&text[end..]) => {
+                    Kind::Period(digits.parse().unwrap(), Precision::Decade)
+                },
+                _ => Kind::Number,
+            };
+
+            let end = match kind {
+                Kind::Period(..) => end + unknown,
+                _ => end,
+            };
+
+            tokens.push(Token { kind, text: digits, end });
+            start = end;
+        } else if b.is_ascii_alphabetic() {
+            let end = start + bytes[start..].iter().take_while(|b| b.is_ascii_alphabetic()).count();
+            tokens.push(Token { kind: Kind::Word, text: &text[start..end], end });
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+
+    tokens
+}
+
+fn is_decade_suffix(text: &str) -> bool {
+    let suffix = text.strip_prefix('\'').unwrap_or(text);
+    let mut chars = suffix.chars();
+    chars.next() == Some('s') && chars.next().is_none_or(|c| !c.is_alphanumeric())
+}
+
+fn parse_iso_suffix(year: u32, text: &str) -> Option<PublishDate> {
+    let separator = text.chars().next().filter(|c| matches!(c, '-' | '/' | '.'))?;
+    let mut parts = text[1..].splitn(3, separator);
+
+    let month = parse_small_number(parts.next()?).filter(|m| (1..=12).contains(m))?;
+    match parts.next().and_then(parse_small_number) {
+        Some(day) => PublishDate::day(year, month, day),
+        None => Some(PublishDate::month(year, month)),
+    }
+}
+
+/// Parses the one or two leading digits of the text, if not followed by another digit.
+fn parse_small_number(text: &str) -> Option<u32> {
+    let len = text.bytes().take_while(u8::is_ascii_digit).count();
+    if len == 0 || len > 2 {
+        return None;
+    }
+    text[..len].parse().ok()
+}
+
+fn is_word(token: Token) -> bool {
+    matches!(token.kind, Kind::Word)
+}
+
+fn is_day(token: Token) -> bool {
+    matches!(token.kind, Kind::Number) && token.text.len() <= 2
+}
+
+fn month_number(word: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "january", "february", "march", "april", "may", "june",
+        "july", "august", "september", "october", "november", "december",
+    ];
+
+    let word = word.to_ascii_lowercase();
+    if word.len() < 3 {
+        return None;
+    }
+
+    MONTHS.iter()
+        .position(|month| month.starts_with(&word) || (word == "sept" && *month == "september"))
+        .map(|i| i as u32 + 1)
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dump_values() {
+        use Precision::*;
+
+        type Expected = Option<(u32, Option<&'static str>, Precision)>;
+
+        let tests: &[(&str, Expected)] = &[
+            ("1997", Some((1997, Some("1997"), Year))),
+            ("December 1993", Some((1993, Some("1993-12"), Month))),
+            ("July 2, 1999", Some((1999, Some("1999-07-02"), Day))),
+            ("December 31, 1992", Some((1992, Some("1992-12-31"), Day))),
+            ("March 5, 2001 (reprint 2004)", Some((2001, Some("2001-03-05"), Day))),
+            ("5 March 2001", Some((2001, Some("2001-03-05"), Day))),
+            ("Sept. 1990", Some((1990, Some("1990-09"), Month))),
+            ("Dec 1993", Some((1993, Some("1993-12"), Month))),
+            ("1987?", Some((1987, Some("1987"), Year))),
+            ("[1985]", Some((1985, Some("1985"), Year))),
+            ("c1990", Some((1990, Some("1990"), Year))),
+            ("c. 1990", Some((1990, Some("1990"), Year))),
+            ("©1990", Some((1990, Some("1990"), Year))),
+            ("1990-1995", Some((1990, Some("1990"), Year))),
+            ("1st ed. 1990", Some((1990, Some("1990"), Year))),
+            ("2001-03-05", Some((2001, Some("2001-03-05"), Day))),
+            ("2001-03", Some((2001, Some("2001-03"), Month))),
+            ("12/31/1992", Some((1992, Some("1992-12-31"), Day))),
+            ("31/12/1992", Some((1992, Some("1992-12-31"), Day))),
+            ("02/03/1992", Some((1992, Some("1992"), Year))),
+            ("February 29, 1996", Some((1996, Some("1996-02-29"), Day))),
+            ("February 30, 1996", Some((1996, Some("1996"), Year))),
+            ("199-", Some((1990, None, Decade))),
+            ("[199-?]", Some((1990, None, Decade))),
+            ("1990s", Some((1990, None, Decade))),
+            ("1980's", Some((1980, None, Decade))),
+            ("19--", Some((1900, None, Century))),
+            ("18uu", Some((1800, None, Century))),
+            ("n.d.", None),
+            ("", None),
+            ("197", None),
+        ];
+
+        for (text, expected) in tests {
+            let date = parse(text);
+            let actual = date.map(|d| (d.year, d.to_iso(), d.precision));
+            let expected = expected.map(|(y, iso, p)| (y, iso.map(ToOwned::to_owned), p));
+            assert_eq!(actual, expected, "while parsing {:?}", text);
+        }
+    }
+}
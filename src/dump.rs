@@ -0,0 +1,190 @@
+use std::fs::File;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::{fmt, str};
+
+use anyhow::Context;
+use flate2::bufread::GzDecoder;
+
+/// A row of an Open Library dump.
+///
+/// The dumps are made of five tab-separated columns, without any header and without
+/// any quoting: the type, the key, the revision, the last modification date and the JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    /// The line number of this row in the dump, starting from one.
+    pub line: u64,
+    /// The type of the record, e.g. `/type/edition`.
+    pub kind: String,
+    /// The key of the record, e.g. `/books/OL10000135M`.
+    pub key: String,
+    pub revision: u64,
+    pub last_modified: String,
+    pub json: String,
+}
+
+impl Row {
+    /// Returns the row as it was written in the dump, without the line ending.
+    pub fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\t{}\t{}", self.kind, self.key, self.revision, self.last_modified, self.json)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The row can't be read, the reader can continue with the next one.
+    InvalidRow { line: u64, error: String, raw: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::InvalidRow { line, error, .. } => write!(f, "invalid row at line {}: {}", line, error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+/// Reads the rows of an Open Library dump.
+pub struct DumpReader<R> {
+    reader: io::BufReader<R>,
+    buffer: Vec<u8>,
+    line: u64,
+}
+
+impl<R: io::Read> DumpReader<R> {
+    pub fn new(reader: R) -> DumpReader<R> {
+        DumpReader {
+            reader: io::BufReader::with_capacity(1024 * 1024, reader),
+            buffer: Vec::new(),
+            line: 0,
+        }
+    }
+
+    /// Reads the next row of the dump, returns `None` once the dump is entirely read.
+    pub fn read_row(&mut self) -> Result<Option<Row>, Error> {
+        self.buffer.clear();
+        if self.reader.read_until(b'\n', &mut self.buffer)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+
+        let mut bytes = &self.buffer[..];
+        bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+        bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+
+        let line = self.line;
+        let invalid = |error: String| Error::InvalidRow {
+            line,
+            error,
+            raw: String::from_utf8_lossy(bytes).into_owned(),
+        };
+
+        let text = str::from_utf8(bytes).map_err(|e| invalid(e.to_string()))?;
+        let mut columns = text.splitn(5, '\t');
+        let mut column = |name: &str| {
+            columns.next().ok_or_else(|| invalid(format!("missing the {} column", name)))
+        };
+
+        let kind = column("type")?;
+        let key = column("key")?;
+        let revision = column("revision")?;
+        let last_modified = column("last modified")?;
+        let json = column("json")?;
+
+        let revision = revision.parse().map_err(|e| invalid(format!("invalid revision {:?}: {}", revision, e)))?;
+
+        Ok(Some(Row {
+            line,
+            kind: kind.to_owned(),
+            key: key.to_owned(),
+            revision,
+            last_modified: last_modified.to_owned(),
+            json: json.to_owned(),
+        }))
+    }
+}
+
+impl<R: io::Read> Iterator for DumpReader<R> {
+    type Item = Result<Row, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_row().transpose()
+    }
+}
+
+/// Opens a dump file, decompressing it if its extension is `.gz`.
+pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Box<dyn io::Read + Send>> {
+    let path = path.as_ref();
+    let is_gzipped = path.extension().is_some_and(|e| e == "gz");
+    let file = File::open(path).with_context(|| format!("while opening {:?}", path.display()))?;
+    if is_gzipped {
+        Ok(Box::new(GzDecoder::new(io::BufReader::new(file))))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::SAMPLE;
+
+    #[test]
+    fn read_sample_dataset() {
+        let rows: Vec<_> = DumpReader::new(SAMPLE.as_bytes()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(rows.len(), 200);
+
+        // The dumps have no header, the first line is a record.
+        let first = &rows[0];
+        assert_eq!(first.line, 1);
+        assert_eq!(first.kind, "/type/page");
+        assert_eq!(first.key, "/about/oregon");
+        assert_eq!(first.revision, 3);
+        assert_eq!(first.last_modified, "2008-07-30T16:43:55.392702");
+
+        let count = |kind: &str| rows.iter().filter(|r| r.kind == kind).count();
+        assert_eq!(count("/type/author"), 97);
+        assert_eq!(count("/type/edition"), 100);
+        assert_eq!(count("/type/redirect"), 2);
+
+        for (row, line) in rows.iter().zip(SAMPLE.lines()) {
+            assert_eq!(row.to_line(), line);
+            serde_json::from_str::<serde_json::Value>(&row.json).unwrap();
+        }
+    }
+
+    #[test]
+    fn keep_quotes_in_the_json_column() {
+        let text = "/type/author\t/authors/OL1A\t1\t2008\t{\"name\": \"\\\"Bob\\\"\\tSmith\"}";
+        let row = DumpReader::new(text.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(row.json, "{\"name\": \"\\\"Bob\\\"\\tSmith\"}");
+    }
+
+    #[test]
+    fn continue_after_invalid_rows() {
+        let text = "/type/author\t/authors/OL1A\t1\r\n\
+                    /type/author\t/authors/OL2A\tone\t2008\t{}\n\
+                    /type/author\t/authors/OL3A\t1\t2008\t{}\r\n";
+        let mut reader = DumpReader::new(text.as_bytes());
+
+        match reader.read_row() {
+            Err(Error::InvalidRow { line: 1, raw, .. }) => assert_eq!(raw, "/type/author\t/authors/OL1A\t1"),
+            otherwise => panic!("expected an invalid row, found {:?}", otherwise),
+        }
+        assert!(matches!(reader.read_row(), Err(Error::InvalidRow { line: 2, .. })));
+
+        let row = reader.read_row().unwrap().unwrap();
+        assert_eq!((row.line, row.key.as_str(), row.json.as_str()), (3, "/authors/OL3A", "{}"));
+        assert!(reader.read_row().unwrap().is_none());
+    }
+}
@@ -0,0 +1,905 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+use std::{io, thread, vec};
+
+use anyhow::anyhow;
+use crossbeam_channel::{bounded, Receiver, Sender};
+use serde::Serialize;
+
+use crate::model::{Author, AuthorKey, Edition, LanguageKey, Record, Text, Work, WorkAuthor, WorkKey};
+use crate::object::{OutAuthor, OutObject};
+use crate::sink::{Encoder, OwnedEncoder, Sink};
+use crate::store::{LookupStore, StoreReader, Table};
+use crate::{date, dump, format, isbn, pipeline};
+
+/// The options that customize the exported documents.
+#[derive(Debug, Default)]
+pub struct Options {
+    /// The identifier schemes to export, all of them if `None`.
+    pub identifier_schemes: Option<Vec<Cow<'static, str>>>,
+    /// Whether the authors are exported as `{ id, name }` objects instead of bare names.
+    pub nested_authors: bool,
+    /// Whether the editions without authors inherit the authors of their work.
+    pub work_authors_fallback: bool,
+    /// The types of documents to export, all of them if empty.
+    pub types: Vec<DocumentType>,
+}
+
+impl Options {
+    pub fn includes(&self, kind: DocumentType) -> bool {
+        self.types.is_empty() || self.types.contains(&kind)
+    }
+}
+
+/// The types of documents exported from the dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentType {
+    Book,
+    Author,
+    Work,
+}
+
+impl DocumentType {
+    /// The types of documents in the order they are exported.
+    pub const ALL: [DocumentType; 3] = [DocumentType::Book, DocumentType::Author, DocumentType::Work];
+
+    /// The name of the type, as found in the `type` field of the documents.
+    pub fn name(self) -> &'static str {
+        match self {
+            DocumentType::Book => "book",
+            DocumentType::Author => "author",
+            DocumentType::Work => "work",
+        }
+    }
+}
+
+impl FromStr for DocumentType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DocumentType, String> {
+        match s {
+            "book" => Ok(DocumentType::Book),
+            "author" => Ok(DocumentType::Author),
+            "work" => Ok(DocumentType::Work),
+            _ => Err(format!("invalid document type {:?}, expected book, author or work", s)),
+        }
+    }
+}
+
+/// The maximum number of redirects we follow before considering the chain broken.
+const MAX_REDIRECTS: usize = 16;
+
+/// Follows the redirections of the given key (e.g. `/authors/OL1015899A`)
+/// and returns the final key, or `None` if the redirect chain loops.
+pub fn resolve_redirects<'t>(
+    reader: &'t dyn StoreReader,
+    key: Cow<'t, str>,
+) -> anyhow::Result<Option<Cow<'t, str>>>
+{
+    let mut visited: Vec<&'t str> = Vec::new();
+    while let Some(location) = reader.get(Table::Redirects, visited.last().copied().unwrap_or(&key))? {
+        if location == key || visited.contains(&location) || visited.len() > MAX_REDIRECTS {
+            return Ok(None);
+        }
+        visited.push(location);
+    }
+    Ok(Some(visited.pop().map_or(key, Cow::Borrowed)))
+}
+
+/// Removes the prefix of a key (e.g. `/authors/`) while keeping it borrowed if possible.
+fn strip_key_prefix<'t>(key: Cow<'t, str>, prefix: &str) -> Option<Cow<'t, str>> {
+    match key {
+        Cow::Borrowed(key) => key.strip_prefix(prefix).map(Cow::Borrowed),
+        Cow::Owned(key) => key.strip_prefix(prefix).map(|key| Cow::Owned(key.to_owned())),
+    }
+}
+
+/// Follows the redirections of the given key, counting the redirect cycles in the summary.
+fn resolve_key<'t>(
+    reader: &'t dyn StoreReader,
+    key: Cow<'t, str>,
+    summary: &mut Summary,
+) -> anyhow::Result<Option<Cow<'t, str>>>
+{
+    let resolved = resolve_redirects(reader, key)?;
+    if resolved.is_none() {
+        summary.redirect_cycles += 1;
+    }
+    Ok(resolved)
+}
+
+/// Resolves the given author key to the author id and name, following the redirects.
+fn resolve_author<'t>(
+    reader: &'t dyn StoreReader,
+    key: Cow<'t, str>,
+    summary: &mut Summary,
+) -> anyhow::Result<Option<(Cow<'t, str>, &'t str)>>
+{
+    match resolve_key(reader, key, summary)?.and_then(|k| strip_key_prefix(k, "/authors/")) {
+        Some(author_id) => {
+            let name = reader.get(Table::AuthorsIdsNames, &author_id)?;
+            Ok(name.map(|name| (author_id, name)))
+        },
+        None => Ok(None),
+    }
+}
+
+/// Resolves the given author keys, in order, ignoring the unknown authors.
+fn resolve_authors<'t>(
+    reader: &'t dyn StoreReader,
+    keys: impl IntoIterator<Item = Cow<'t, str>>,
+    nested: bool,
+    summary: &mut Summary,
+) -> anyhow::Result<Vec<OutAuthor<'t>>>
+{
+    let mut authors = Vec::new();
+    for key in keys {
+        if let Some((id, name)) = resolve_author(reader, key, summary)? {
+            authors.push(OutAuthor::new(id, Cow::Borrowed(name), nested));
+        }
+    }
+    Ok(authors)
+}
+
+/// Returns `true` if every author and work the edition references is already known,
+/// in which case it can be exported without waiting for the end of the dump.
+///
+/// The languages don't hold the editions back, the ones that aren't known yet are exported as their code.
+fn is_resolvable(reader: &dyn StoreReader, book: &Edition, options: &Options) -> anyhow::Result<bool> {
+    let is_known_author = |key: &str| -> anyhow::Result<bool> {
+        match key.strip_prefix("/authors/") {
+            Some(author_id) => Ok(reader.get(Table::AuthorsIdsNames, author_id)?.is_some()),
+            None => Ok(false),
+        }
+    };
+
+    for AuthorKey { key } in book.authors.iter().flatten() {
+        if !is_known_author(key)? {
+            return Ok(false);
+        }
+    }
+
+    if let Some(WorkKey { key }) = book.works.iter().flatten().next() {
+        let json = match key.strip_prefix("/works/") {
+            Some(work_id) => reader.get(Table::WorksIdsJsons, work_id)?,
+            None => None,
+        };
+
+        match json {
+            Some(json) if options.work_authors_fallback && book.authors.as_ref().is_none_or(Vec::is_empty) => {
+                if let Ok(work) = serde_json::from_str::<Work>(json) {
+                    for key in work.authors.iter().flatten().map(WorkAuthor::key) {
+                        if !is_known_author(key)? {
+                            return Ok(false);
+                        }
+                    }
+                }
+            },
+            Some(_) => (),
+            None => return Ok(false),
+        }
+    }
+
+    Ok(true)
+}
+
+/// Converts an edition into a book, resolving its authors and work.
+/// The invalid ISBNs and the redirect cycles are counted in the summary.
+pub fn book_object<'t>(
+    reader: &'t dyn StoreReader,
+    options: &Options,
+    book_id: &'t str,
+    book: Edition<'t>,
+    summary: &mut Summary,
+) -> anyhow::Result<OutObject<'t>>
+{
+    let mut identifiers = book.identifiers.unwrap_or_default();
+    let top_level_identifiers = vec![
+        ("oclc_numbers", book.oclc_numbers.unwrap_or_default()),
+        ("lccn", book.lccn.unwrap_or_default()),
+        ("ocaid", book.ocaid.into_iter().collect()),
+    ];
+    for (scheme, values) in top_level_identifiers {
+        let entry = identifiers.entry(Cow::Borrowed(scheme)).or_default();
+        for value in values {
+            if !entry.contains(&value) {
+                entry.push(value);
+            }
+        }
+    }
+    identifiers.retain(|scheme, values| {
+        !values.is_empty() && options.identifier_schemes.as_ref().is_none_or(|s| s.contains(scheme))
+    });
+
+    let mut isbns = Vec::new();
+    for raw in book.isbn_13.iter().chain(&book.isbn_10).flatten() {
+        match isbn::normalize(raw) {
+            Some(isbn) if !isbns.contains(&isbn) => isbns.push(isbn),
+            Some(_) => (),
+            None => summary.invalid_isbns += 1,
+        }
+    }
+
+    let work_id = match book.works.and_then(|works| works.into_iter().next()) {
+        Some(WorkKey { key }) => {
+            resolve_key(reader, key, summary)?
+                .and_then(|key| strip_key_prefix(key, "/works/"))
+        },
+        None => None,
+    };
+
+    // The work of the edition, only fetched to inherit its authors.
+    let work = match &work_id {
+        Some(work_id) if options.work_authors_fallback && book.authors.as_ref().is_none_or(Vec::is_empty) => {
+            reader.get(Table::WorksIdsJsons, work_id)?
+                .and_then(|json| serde_json::from_str::<Work>(json).ok())
+        },
+        _ => None,
+    };
+
+    let author_keys = book.authors.into_iter().flatten().map(|AuthorKey { key }| key)
+        .chain(work.and_then(|work| work.authors).into_iter().flatten().map(WorkAuthor::into_key));
+    let authors = resolve_authors(reader, author_keys, options.nested_authors, summary)?;
+
+    // The languages missing from the dump are kept as their code.
+    let mut languages = Vec::new();
+    let codes = book.languages.into_iter().flatten().filter_map(|LanguageKey { key }| strip_key_prefix(key, "/languages/"));
+    for code in codes {
+        match reader.get(Table::LanguagesIdsNames, &code)? {
+            Some(name) => languages.push(Cow::Borrowed(name)),
+            None => languages.push(code),
+        }
+    }
+
+    let publish_date = book.publish_date.as_deref().and_then(date::parse);
+    let format = book.physical_format.as_deref().map(format::Format::from_physical_format);
+
+    Ok(OutObject::Book {
+        id: Cow::Borrowed(book_id),
+        name: book.title,
+        subtitle: book.subtitle,
+        work_id,
+        authors,
+        publish_year: publish_date.map(|d| d.year),
+        publish_date: publish_date.and_then(|d| d.to_iso()),
+        publish_date_precision: publish_date.map(|d| d.precision),
+        number_of_pages: book.number_of_pages,
+        publishers: book.publishers.unwrap_or_default(),
+        format,
+        physical_format: book.physical_format,
+        subjects: book.subjects.unwrap_or_default(),
+        languages,
+        isbns,
+        identifiers,
+    })
+}
+
+/// Converts an author into its output document.
+pub fn author_object<'t>(id: &'t str, author: Author<'t>) -> OutObject<'t> {
+    let birth_year = author.birth_date.as_deref().and_then(date::parse).map(|d| d.year);
+    let death_year = author.death_date.as_deref().and_then(date::parse).map(|d| d.year);
+
+    OutObject::Author {
+        id: Cow::Borrowed(id),
+        name: author.name,
+        personal_name: author.personal_name,
+        alternate_names: author.alternate_names.unwrap_or_default(),
+        birth_date: author.birth_date,
+        birth_year,
+        death_date: author.death_date,
+        death_year,
+        bio: author.bio.map(Text::into_inner),
+        // removed photos are marked with a negative id
+        photos: author.photos.unwrap_or_default().into_iter().filter(|id| *id > 0).collect(),
+        remote_ids: author.remote_ids.unwrap_or_default(),
+    }
+}
+
+/// Converts a work into its output document, resolving its authors.
+/// The redirect cycles are counted in the summary.
+pub fn work_object<'t>(
+    reader: &'t dyn StoreReader,
+    options: &Options,
+    id: &'t str,
+    work: Work<'t>,
+    summary: &mut Summary,
+) -> anyhow::Result<OutObject<'t>>
+{
+    let author_keys = work.authors.into_iter().flatten().map(WorkAuthor::into_key);
+    let authors = resolve_authors(reader, author_keys, options.nested_authors, summary)?;
+
+    Ok(OutObject::Work {
+        id: Cow::Borrowed(id),
+        name: work.title,
+        authors,
+        subjects: work.subjects.unwrap_or_default(),
+        description: work.description.map(Text::into_inner),
+        first_publish_date: work.first_publish_date,
+    })
+}
+
+/// The number of records or documents processed at once by a worker thread.
+pub const BATCH_SIZE: usize = 4096;
+
+/// A record of the dump that couldn't be read or parsed.
+#[derive(Debug, Serialize)]
+pub struct Reject {
+    pub line: u64,
+    pub error: String,
+    /// The raw line of the record.
+    pub record: String,
+}
+
+impl Reject {
+    pub fn new(row: &dump::Row, error: impl ToString) -> Reject {
+        Reject { line: row.line, error: error.to_string(), record: row.to_line() }
+    }
+}
+
+/// What a worker extracted from a batch of records of the dump.
+struct RecordsBatch<A> {
+    /// The books that could be resolved.
+    output: A,
+    /// The entries to insert into the stores.
+    entries: Vec<(Table, String, String)>,
+    rejects: Vec<Reject>,
+    summary: Summary,
+}
+
+/// Parses a batch of records of the dump, exports the books that can already
+/// be resolved, if asked to, and returns the entries to store for the other records.
+fn process_records<A: Default>(
+    store: &dyn LookupStore,
+    options: &Options,
+    export: bool,
+    encode: &dyn Fn(&mut A, OutObject) -> anyhow::Result<()>,
+    (rows, rejects): (Vec<dump::Row>, Vec<Reject>),
+) -> anyhow::Result<RecordsBatch<A>>
+{
+    let mut batch = RecordsBatch {
+        output: A::default(),
+        entries: Vec::new(),
+        rejects,
+        summary: Summary {
+            last_modified: rows.iter().map(|row| &row.last_modified).max().cloned(),
+            ..Summary::default()
+        },
+    };
+    let export_books = export && options.includes(DocumentType::Book);
+
+    store.read(&mut |reader| {
+        for row in &rows {
+            let record = match Record::parse(&row.kind, &row.json) {
+                Ok(record) => record,
+                Err(e) => {
+                    batch.rejects.push(Reject::new(row, e));
+                    continue;
+                },
+            };
+
+            match record {
+                Record::Redirect(redirect) => {
+                    batch.entries.push((Table::Redirects, row.key.clone(), redirect.location.into_owned()));
+                },
+                // The books are not exported at all when not asked for.
+                Record::Edition(_) if export && !export_books => (),
+                Record::Edition(book) => if let Some(book_id) = row.key.strip_prefix("/books/") {
+                    // The editions that reference authors or works we didn't see yet
+                    // are kept aside and exported once the whole dump is read.
+                    if export_books && is_resolvable(reader, &book, options)? {
+                        encode(&mut batch.output, book_object(reader, options, book_id, book, &mut batch.summary)?)?;
+                    } else {
+                        batch.entries.push((Table::Editions, book_id.to_owned(), row.json.clone()));
+                    }
+                },
+                Record::Author(author) => if let Some(author_id) = row.key.strip_prefix("/authors/") {
+                    batch.entries.push((Table::AuthorsIdsNames, author_id.to_owned(), author.name.into_owned()));
+                    batch.entries.push((Table::AuthorsIdsJsons, author_id.to_owned(), row.json.clone()));
+                },
+                Record::Work(_) => if let Some(work_id) = row.key.strip_prefix("/works/") {
+                    batch.entries.push((Table::WorksIdsJsons, work_id.to_owned(), row.json.clone()));
+                },
+                Record::Language(language) => if let Some(language_id) = row.key.strip_prefix("/languages/") {
+                    batch.entries.push((Table::LanguagesIdsNames, language_id.to_owned(), language.name.into_owned()));
+                },
+                Record::Other => (),
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(batch)
+}
+
+/// Sends the entries of the store, in batches, from a new read transaction.
+fn produce_entries(
+    store: &dyn LookupStore,
+    table: Table,
+    send: &mut dyn FnMut(Vec<(String, String)>) -> anyhow::Result<()>,
+) -> anyhow::Result<()>
+{
+    store.read(&mut |reader| {
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        for result in reader.iter(table)? {
+            let (key, value) = result?;
+            batch.push((key.to_owned(), value.to_owned()));
+            if batch.len() == BATCH_SIZE {
+                send(std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE)))?;
+            }
+        }
+        if !batch.is_empty() {
+            send(batch)?;
+        }
+        Ok(())
+    })
+}
+
+/// What was found while reading a dump or exporting documents.
+#[derive(Debug, Default, Clone)]
+pub struct Summary {
+    /// The number of ISBNs ignored because of an invalid checksum.
+    pub invalid_isbns: usize,
+    /// The number of references to keys whose redirects loop, they are ignored.
+    pub redirect_cycles: usize,
+    /// The most recent modification date of the records of the dump.
+    pub last_modified: Option<String>,
+}
+
+impl Summary {
+    /// Adds the counts of another summary, keeping the most recent modification date.
+    pub fn merge(&mut self, other: Summary) {
+        self.invalid_isbns += other.invalid_isbns;
+        self.redirect_cycles += other.redirect_cycles;
+        self.last_modified = self.last_modified.take().max(other.last_modified);
+    }
+}
+
+/// Extracts the documents of a dump into a sink with the help of a lookup store.
+pub struct Extractor<'s> {
+    store: &'s dyn LookupStore,
+    options: Options,
+    threads: usize,
+}
+
+impl<'s> Extractor<'s> {
+    pub fn new(store: &'s dyn LookupStore, options: Options) -> Extractor<'s> {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Extractor { store, options, threads }
+    }
+
+    /// Changes the number of worker threads, all the available cores by default.
+    pub fn threads(mut self, threads: usize) -> Extractor<'s> {
+        self.threads = threads;
+        self
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Reads the dump into the store, the invalid records are given to `on_reject`.
+    ///
+    /// When `export` is `true` the editions that can already be resolved are exported,
+    /// the others are kept in the store to be exported once the whole dump is read.
+    pub fn read_dump<S: Sink>(
+        &self,
+        reader: impl io::Read + Send,
+        export: bool,
+        mut on_reject: impl FnMut(Reject) -> anyhow::Result<()>,
+        sink: &mut S,
+    ) -> anyhow::Result<Summary>
+    {
+        let mut summary = Summary::default();
+        let encoder = sink.encoder();
+        let encode = |batch: &mut _, object: OutObject| encoder.encode(batch, object);
+
+        pipeline::run(
+            self.threads,
+            |send| {
+                let mut reader = dump::DumpReader::new(reader);
+                let mut rows = Vec::with_capacity(BATCH_SIZE);
+                let mut rejects = Vec::new();
+                loop {
+                    match reader.read_row() {
+                        Ok(Some(row)) => rows.push(row),
+                        Ok(None) => break,
+                        Err(dump::Error::Io(e)) => return Err(e.into()),
+                        Err(dump::Error::InvalidRow { line, error, raw }) => {
+                            rejects.push(Reject { line, error, record: raw });
+                        },
+                    }
+
+                    if rows.len() + rejects.len() >= BATCH_SIZE {
+                        let rows = std::mem::replace(&mut rows, Vec::with_capacity(BATCH_SIZE));
+                        send((rows, std::mem::take(&mut rejects)))?;
+                    }
+                }
+                if !rows.is_empty() || !rejects.is_empty() {
+                    send((rows, rejects))?;
+                }
+                Ok(())
+            },
+            |batch| process_records(self.store, &self.options, export, &encode, batch),
+            |batch| {
+                sink.write(batch.output)?;
+                summary.merge(batch.summary);
+
+                for reject in batch.rejects {
+                    on_reject(reject)?;
+                }
+
+                // The entries are committed right away to be seen by the next batches.
+                self.store.write(&batch.entries)
+            },
+        )?;
+
+        Ok(summary)
+    }
+
+    /// Reads the dump into the store and keeps all the editions, e.g. to build an index.
+    pub fn index(&self, reader: impl io::Read + Send, on_reject: impl FnMut(Reject) -> anyhow::Result<()>) -> anyhow::Result<Summary> {
+        self.read_dump(reader, false, on_reject, &mut Discard)
+    }
+
+    /// Exports the documents of the given type kept in the store, if the options include it.
+    pub fn export<S: Sink>(&self, kind: DocumentType, sink: &mut S) -> anyhow::Result<Summary> {
+        let mut summary = Summary::default();
+        if !self.options.includes(kind) {
+            return Ok(summary);
+        }
+
+        let (store, options) = (self.store, &self.options);
+        let encoder = sink.encoder();
+        let table = match kind {
+            DocumentType::Book => Table::Editions,
+            DocumentType::Author => Table::AuthorsIdsJsons,
+            DocumentType::Work => Table::WorksIdsJsons,
+        };
+
+        pipeline::run(
+            self.threads,
+            |send| produce_entries(store, table, send),
+            |entries| {
+                let (mut output, mut batch_summary) = (Default::default(), Summary::default());
+                store.read(&mut |reader| {
+                    for (id, json) in &entries {
+                        let object = match kind {
+                            DocumentType::Book => {
+                                let book = serde_json::from_str(json)?;
+                                book_object(reader, options, id, book, &mut batch_summary)?
+                            },
+                            DocumentType::Author => author_object(id, serde_json::from_str(json)?),
+                            DocumentType::Work => work_object(reader, options, id, serde_json::from_str(json)?, &mut batch_summary)?,
+                        };
+                        encoder.encode(&mut output, object)?;
+                    }
+                    Ok(())
+                })?;
+                Ok((output, batch_summary))
+            },
+            |(output, batch_summary)| {
+                summary.merge(batch_summary);
+                sink.write(output)
+            },
+        )?;
+
+        Ok(summary)
+    }
+
+    /// Reads the dump and exports all of its documents into the sink, then finishes it.
+    pub fn extract<S: Sink>(
+        &self,
+        reader: impl io::Read + Send,
+        on_reject: impl FnMut(Reject) -> anyhow::Result<()>,
+        sink: &mut S,
+    ) -> anyhow::Result<Summary>
+    {
+        let mut summary = self.read_dump(reader, true, on_reject, sink)?;
+        for kind in DocumentType::ALL.iter() {
+            summary.merge(self.export(*kind, sink)?);
+        }
+        sink.finish()?;
+        Ok(summary)
+    }
+}
+
+/// A sink that ignores the documents.
+struct Discard;
+
+impl Sink for Discard {
+    type Encoder = OwnedEncoder;
+
+    fn encoder(&self) -> OwnedEncoder {
+        OwnedEncoder
+    }
+
+    fn write(&mut self, _batch: Vec<OutObject<'static>>) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sends the batches of documents to the `Objects` iterator.
+struct ChannelSink {
+    sender: Sender<anyhow::Result<Vec<OutObject<'static>>>>,
+}
+
+impl Sink for ChannelSink {
+    type Encoder = OwnedEncoder;
+
+    fn encoder(&self) -> OwnedEncoder {
+        OwnedEncoder
+    }
+
+    fn write(&mut self, batch: Vec<OutObject<'static>>) -> anyhow::Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.sender.send(Ok(batch)).map_err(|_| anyhow!("the iterator was dropped"))
+    }
+}
+
+/// An iterator over the documents of a dump, the extraction runs on background threads.
+///
+/// The books that can be resolved while reading the dump come first, followed by
+/// the remaining books, the authors and the works. The invalid records are skipped.
+pub struct Objects {
+    receiver: Receiver<anyhow::Result<Vec<OutObject<'static>>>>,
+    batch: vec::IntoIter<OutObject<'static>>,
+}
+
+impl Objects {
+    pub fn new(
+        reader: impl io::Read + Send + 'static,
+        store: Box<dyn LookupStore + Send>,
+        options: Options,
+        threads: usize,
+    ) -> Objects
+    {
+        let (sender, receiver) = bounded(threads.max(1) * 2);
+
+        thread::spawn(move || {
+            let extractor = Extractor::new(&*store, options).threads(threads);
+            let mut sink = ChannelSink { sender };
+            if let Err(e) = extractor.extract(reader, |_| Ok(()), &mut sink) {
+                let _ = sink.sender.send(Err(e));
+            }
+        });
+
+        Objects { receiver, batch: Vec::new().into_iter() }
+    }
+}
+
+impl Iterator for Objects {
+    type Item = anyhow::Result<OutObject<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(object) = self.batch.next() {
+                return Some(Ok(object));
+            }
+            match self.receiver.recv() {
+                Ok(Ok(batch)) => self.batch = batch.into_iter(),
+                Ok(Err(e)) => return Some(Err(e)),
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::{NdJsonEncoder, NdJsonSink};
+    use crate::store::MemoryStore;
+    use crate::testing::{extract_dump, SAMPLE};
+
+    fn extract(options: Options) -> Vec<OutObject<'static>> {
+        let store = Box::new(MemoryStore::new());
+        Objects::new(SAMPLE.as_bytes(), store, options, 2).collect::<anyhow::Result<_>>().unwrap()
+    }
+
+    /// Writes the records, given as their type, key and JSON, in the format of the dump.
+    fn dump(records: &[(&str, &str, &str)]) -> Vec<u8> {
+        let rows = records.iter().map(|(kind, key, json)| format!("{}\t{}\t1\t2021-01-01T00:00:00.000000\t{}\n", kind, key, json));
+        rows.collect::<String>().into_bytes()
+    }
+
+    /// The ids and the authors' names of the books of the objects.
+    fn books_authors(objects: &[OutObject]) -> Vec<(String, Vec<String>)> {
+        let books = objects.iter().filter_map(|object| match object {
+            OutObject::Book { id, authors, .. } => {
+                let names = authors.iter().map(|author| match author {
+                    OutAuthor::Name(name) | OutAuthor::Object { name, .. } => name.to_string(),
+                });
+                Some((id.to_string(), names.collect()))
+            },
+            _ => None,
+        });
+        books.collect()
+    }
+
+    #[test]
+    fn extract_sample_dataset() {
+        let objects = extract(Options::default());
+
+        let books: Vec<_> = objects.iter().filter(|o| matches!(o, OutObject::Book { .. })).collect();
+        let authors = objects.iter().filter(|o| matches!(o, OutObject::Author { .. })).count();
+        assert_eq!((books.len(), authors, objects.len()), (100, 97, 197));
+
+        let book = books.iter().find(|o| matches!(o, OutObject::Book { id, .. } if id == "OL10000135M")).unwrap();
+        match book {
+            OutObject::Book { work_id, publish_year, isbns, format, .. } => {
+                assert_eq!(work_id.as_deref(), Some("OL7925046W"));
+                assert_eq!(*publish_year, Some(1993));
+                assert_eq!(isbns, &["9780107805401"]);
+                assert_eq!(*format, Some(format::Format::Hardcover));
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn follow_the_redirects() {
+        let store = MemoryStore::new();
+        let mut entries = vec![
+            (Table::Redirects, "/authors/OL1A".to_owned(), "/authors/OL2A".to_owned()),
+            (Table::Redirects, "/authors/OL2A".to_owned(), "/authors/OL3A".to_owned()),
+            (Table::Redirects, "/works/OL1W".to_owned(), "/works/OL2W".to_owned()),
+            (Table::Redirects, "/works/OL2W".to_owned(), "/works/OL1W".to_owned()),
+        ];
+        // A chain too long from its start, but not from its third key.
+        for i in 0..=MAX_REDIRECTS + 1 {
+            entries.push((Table::Redirects, format!("/books/OL{}M", i), format!("/books/OL{}M", i + 1)));
+        }
+        store.write(&entries).unwrap();
+
+        store.read(&mut |reader| {
+            let resolve = |key: &'static str| resolve_redirects(reader, Cow::Borrowed(key)).unwrap();
+            assert_eq!(resolve("/authors/OL1A").as_deref(), Some("/authors/OL3A"));
+            assert_eq!(resolve("/authors/OL3A").as_deref(), Some("/authors/OL3A"));
+            assert_eq!(resolve("/works/OL1W"), None);
+            assert_eq!(resolve("/books/OL0M"), None);
+            assert_eq!(resolve("/books/OL2M").as_deref(), Some("/books/OL18M"));
+            Ok(())
+        }).unwrap();
+    }
+
+    #[test]
+    fn inherit_the_work_authors() {
+        let records = [
+            ("/type/author", "/authors/OL1A", r#"{"name": "Jane Doe"}"#),
+            ("/type/author", "/authors/OL2A", r#"{"name": "John Roe"}"#),
+            // The works reference their authors through a role, or directly by key.
+            ("/type/work", "/works/OL1W", concat!(
+                r#"{"title": "A", "authors": [{"author": {"key": "/authors/OL1A"}, "type": {"key": "/type/author_role"}}, "#,
+                r#"{"key": "/authors/OL2A"}]}"#,
+            )),
+            ("/type/edition", "/books/OL1M", r#"{"title": "A", "works": [{"key": "/works/OL1W"}]}"#),
+            (
+                "/type/edition",
+                "/books/OL2M",
+                r#"{"title": "A", "works": [{"key": "/works/OL1W"}], "authors": [{"key": "/authors/OL2A"}]}"#,
+            ),
+        ];
+        let extract = |work_authors_fallback| {
+            let options = Options { work_authors_fallback, types: vec![DocumentType::Book], ..Options::default() };
+            let objects = Objects::new(io::Cursor::new(dump(&records)), Box::new(MemoryStore::new()), options, 2);
+            let mut books = books_authors(&objects.collect::<anyhow::Result<Vec<_>>>().unwrap());
+            books.sort();
+            books
+        };
+
+        let jane_john = vec!["Jane Doe".to_owned(), "John Roe".to_owned()];
+        let john = vec!["John Roe".to_owned()];
+        assert_eq!(extract(true), [("OL1M".to_owned(), jane_john), ("OL2M".to_owned(), john.clone())]);
+        assert_eq!(extract(false), [("OL1M".to_owned(), vec![]), ("OL2M".to_owned(), john)]);
+    }
+
+    #[test]
+    fn export_the_editions_once_their_references_are_known() {
+        let mut records = vec![
+            ("/type/author", "/authors/OL1A", r#"{"name": "Known Early"}"#),
+            ("/type/redirect", "/authors/OL9A", r#"{"location": "/authors/OL3A"}"#),
+            ("/type/redirect", "/works/OL9W", r#"{"location": "/works/OL1W"}"#),
+        ];
+        // The editions are read in other batches than the first records.
+        records.extend(std::iter::repeat_n(("/type/page", "/about", "{}"), 2 * BATCH_SIZE));
+        records.extend_from_slice(&[
+            ("/type/edition", "/books/OL1M", r#"{"title": "A", "authors": [{"key": "/authors/OL1A"}]}"#),
+            ("/type/edition", "/books/OL2M", r#"{"title": "B", "authors": [{"key": "/authors/OL2A"}]}"#),
+            ("/type/edition", "/books/OL3M", r#"{"title": "C", "authors": [{"key": "/authors/OL9A"}]}"#),
+            ("/type/edition", "/books/OL4M", r#"{"title": "D", "works": [{"key": "/works/OL9W"}]}"#),
+            ("/type/author", "/authors/OL2A", r#"{"name": "Known Late"}"#),
+            ("/type/author", "/authors/OL3A", r#"{"name": "Redirected"}"#),
+            ("/type/work", "/works/OL1W", r#"{"title": "D"}"#),
+        ]);
+        let dump = dump(&records);
+
+        let extract = |threads| {
+            let store = MemoryStore::new();
+            let options = Options { types: vec![DocumentType::Book], ..Options::default() };
+            let mut sink = NdJsonSink::new(Vec::new(), NdJsonEncoder::default());
+            Extractor::new(&store, options).threads(threads).extract(dump.as_slice(), |_| Ok(()), &mut sink).unwrap();
+            // The books can be exported in another order depending on the threads.
+            let output = String::from_utf8(sink.into_inner()).unwrap();
+            let mut lines: Vec<_> = output.lines().map(ToOwned::to_owned).collect();
+            lines.sort();
+            lines
+        };
+
+        let books = extract(1);
+        assert_eq!(books, extract(4));
+        assert_eq!(books, [
+            r#"{"type":"book","id":"OL1M","name":"A","authors":["Known Early"]}"#,
+            r#"{"type":"book","id":"OL2M","name":"B","authors":["Known Late"]}"#,
+            r#"{"type":"book","id":"OL3M","name":"C","authors":["Redirected"]}"#,
+            r#"{"type":"book","id":"OL4M","name":"D","work_id":"OL1W"}"#,
+        ]);
+    }
+
+    #[test]
+    fn resolve_the_language_names() {
+        let mut records = vec![("/type/language", "/languages/fre", r#"{"name": "French"}"#)];
+        // The edition is read in another batch than the language.
+        records.extend(std::iter::repeat_n(("/type/page", "/about", "{}"), 2 * BATCH_SIZE));
+        records.push((
+            "/type/edition",
+            "/books/OL1M",
+            r#"{"title": "A", "languages": [{"key": "/languages/fre"}, {"key": "/languages/xyz"}]}"#,
+        ));
+        let options = Options { types: vec![DocumentType::Book], ..Options::default() };
+        let objects = Objects::new(io::Cursor::new(dump(&records)), Box::new(MemoryStore::new()), options, 1);
+        match &objects.collect::<anyhow::Result<Vec<_>>>().unwrap()[..] {
+            [OutObject::Book { languages, .. }] => assert_eq!(languages, &["French", "xyz"]),
+            objects => panic!("unexpected objects {:?}", objects),
+        }
+    }
+
+    #[test]
+    fn export_the_editions_before_their_languages() {
+        let records = [
+            ("/type/edition", "/books/OL1M", r#"{"title": "A", "languages": [{"key": "/languages/fre"}]}"#),
+            ("/type/language", "/languages/fre", r#"{"name": "French"}"#),
+        ];
+        let store = MemoryStore::new();
+        let options = Options { types: vec![DocumentType::Book], ..Options::default() };
+        let mut sink = NdJsonSink::new(Vec::new(), NdJsonEncoder::default());
+        Extractor::new(&store, options).threads(1).extract(dump(&records).as_slice(), |_| Ok(()), &mut sink).unwrap();
+
+        // The edition isn't kept aside until the end of the dump.
+        store.read(&mut |reader| {
+            assert_eq!(reader.len(Table::Editions)?, 0);
+            Ok(())
+        }).unwrap();
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(output.trim_end(), r#"{"type":"book","id":"OL1M","name":"A","languages":["fre"]}"#);
+    }
+
+    #[test]
+    fn count_the_redirect_cycles() {
+        let records = [
+            ("/type/author", "/authors/OL3A", r#"{"name": "Jane Doe"}"#),
+            ("/type/redirect", "/authors/OL1A", r#"{"location": "/authors/OL2A"}"#),
+            ("/type/redirect", "/authors/OL2A", r#"{"location": "/authors/OL1A"}"#),
+            ("/type/edition", "/books/OL1M", r#"{"title": "A", "authors": [{"key": "/authors/OL1A"}]}"#),
+            (
+                "/type/edition",
+                "/books/OL2M",
+                r#"{"title": "B", "authors": [{"key": "/authors/OL2A"}, {"key": "/authors/OL3A"}]}"#,
+            ),
+        ];
+        let mut sink = NdJsonSink::new(Vec::new(), NdJsonEncoder::default());
+        let summary = extract_dump(&dump(&records), Options::default(), &mut sink).unwrap();
+
+        assert_eq!(summary.redirect_cycles, 2);
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert!(output.contains(r#""id":"OL2M","name":"B","authors":["Jane Doe"]"#));
+    }
+
+    #[test]
+    fn only_extract_the_requested_types() {
+        let objects = extract(Options { types: vec![DocumentType::Author], ..Options::default() });
+        assert_eq!(objects.len(), 97);
+        assert!(objects.iter().all(|o| matches!(o, OutObject::Author { .. })));
+    }
+}
@@ -0,0 +1,94 @@
+use serde::Serialize;
+
+/// The normalized physical format of an edition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Paperback,
+    Hardcover,
+    Ebook,
+    Audio,
+    Other,
+}
+
+impl Format {
+    /// Normalizes a free-text `physical_format` (e.g. `"Mass Market Paperback"`, `"Audio CD"`).
+    pub fn from_physical_format(physical_format: &str) -> Format {
+        // The keywords are sequences of whole words, e.g. `"cd"` doesn't match `"cdrom"`.
+        const KEYWORDS: &[(&[&str], Format)] = &[
+            (&["paperback"], Format::Paperback),
+            (&["paper", "back"], Format::Paperback),
+            (&["softcover"], Format::Paperback),
+            (&["soft", "cover"], Format::Paperback),
+            (&["mass", "market"], Format::Paperback),
+            (&["pocket"], Format::Paperback),
+            (&["hardcover"], Format::Hardcover),
+            (&["hard", "cover"], Format::Hardcover),
+            (&["hardback"], Format::Hardcover),
+            (&["library", "binding"], Format::Hardcover),
+            (&["cloth"], Format::Hardcover),
+            (&["clothbound"], Format::Hardcover),
+            // A CD-ROM holds files, not an audio recording.
+            (&["cd", "rom"], Format::Ebook),
+            (&["cdrom"], Format::Ebook),
+            (&["audio"], Format::Audio),
+            (&["audiobook"], Format::Audio),
+            (&["cassette"], Format::Audio),
+            (&["mp3"], Format::Audio),
+            (&["cd"], Format::Audio),
+            (&["ebook"], Format::Ebook),
+            (&["e", "book"], Format::Ebook),
+            (&["electronic"], Format::Ebook),
+            (&["kindle"], Format::Ebook),
+            (&["epub"], Format::Ebook),
+            (&["pdf"], Format::Ebook),
+            (&["digital"], Format::Ebook),
+        ];
+
+        // The bindings are checked first, e.g. `"Paperback with CD"` is a paperback,
+        // and the audio before the digital formats, e.g. `"Digital audio"` is an audio book.
+        let words: Vec<_> = physical_format
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        KEYWORDS.iter()
+            .find(|(keyword, _)| words.windows(keyword.len()).any(|window| window == *keyword))
+            .map_or(Format::Other, |(_, format)| *format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_physical_formats() {
+        let cases = [
+            ("Paperback", Format::Paperback),
+            ("Mass Market Paperback", Format::Paperback),
+            ("paper back", Format::Paperback),
+            ("Trade Paperback with CD", Format::Paperback),
+            ("Hardcover", Format::Hardcover),
+            ("Library Binding", Format::Hardcover),
+            ("Cloth", Format::Hardcover),
+            ("Audio CD", Format::Audio),
+            ("Audio Cassette", Format::Audio),
+            ("MP3 CD", Format::Audio),
+            ("Digital audio", Format::Audio),
+            ("Audiobook", Format::Audio),
+            ("CD-ROM", Format::Ebook),
+            ("E-book", Format::Ebook),
+            ("Kindle Edition", Format::Ebook),
+            ("[electronic resource]", Format::Ebook),
+            ("Board book", Format::Other),
+            ("Spiral-bound", Format::Other),
+            ("Clothing", Format::Other),
+            ("Unknown Binding", Format::Other),
+            ("", Format::Other),
+        ];
+        for (physical_format, format) in cases.iter() {
+            assert_eq!(Format::from_physical_format(physical_format), *format, "{:?}", physical_format);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+/// Normalizes an ISBN-10 or an ISBN-13 into an hyphen-less ISBN-13.
+///
+/// Returns `None` if the ISBN doesn't have the right length or an invalid checksum.
+pub fn normalize(isbn: &str) -> Option<String> {
+    let digits: Vec<u8> = isbn.bytes().filter(|b| !matches!(b, b'-' | b' ')).collect();
+
+    match digits.len() {
+        10 => {
+            let (body, check) = digits.split_at(9);
+            if !body.iter().all(u8::is_ascii_digit) || isbn10_check_digit(body) != check[0].to_ascii_uppercase() {
+                return None;
+            }
+
+            let mut isbn13 = b"978".to_vec();
+            isbn13.extend_from_slice(body);
+            isbn13.push(isbn13_check_digit(&isbn13));
+            String::from_utf8(isbn13).ok()
+        },
+        13 => {
+            let (body, check) = digits.split_at(12);
+            if !body.iter().all(u8::is_ascii_digit) || isbn13_check_digit(body) != check[0] {
+                return None;
+            }
+
+            String::from_utf8(digits).ok()
+        },
+        _ => None,
+    }
+}
+
+/// Computes the check digit of the first nine ASCII digits of an ISBN-10.
+fn isbn10_check_digit(body: &[u8]) -> u8 {
+    let sum: u32 = body.iter().zip((2..=10).rev()).map(|(d, w)| u32::from(d - b'0') * w).sum();
+    match (11 - sum % 11) % 11 {
+        10 => b'X',
+        n => b'0' + n as u8,
+    }
+}
+
+/// Computes the check digit of the first twelve ASCII digits of an ISBN-13.
+fn isbn13_check_digit(body: &[u8]) -> u8 {
+    let sum: u32 = body.iter().zip([1, 3].iter().cycle()).map(|(d, w)| u32::from(d - b'0') * w).sum();
+    b'0' + ((10 - sum % 10) % 10) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_isbns() {
+        let cases = [
+            ("0306406152", Some("9780306406157")),
+            ("080442957X", Some("9780804429573")),
+            ("043942089x", Some("9780439420891")),
+            ("0-306-40615-2", Some("9780306406157")),
+            ("978 0 306 40615 7", Some("9780306406157")),
+            ("978-0-306-40615-7", Some("9780306406157")),
+            ("0306406153", None),
+            ("9780306406158", None),
+            ("X306406152", None),
+            ("030640615", None),
+            ("97803064061577", None),
+            ("", None),
+        ];
+        for (isbn, expected) in cases.iter() {
+            assert_eq!(normalize(isbn).as_deref(), *expected, "{:?}", isbn);
+        }
+    }
+}
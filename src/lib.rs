@@ -0,0 +1,23 @@
+#[cfg(feature = "columnar")]
+pub mod columnar;
+pub mod date;
+pub mod dump;
+pub mod extract;
+pub mod format;
+pub mod isbn;
+#[cfg(feature = "meilisearch")]
+pub mod meilisearch;
+pub mod model;
+pub mod object;
+pub mod pipeline;
+pub mod sink;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
+pub mod tabular;
+#[cfg(test)]
+mod testing;
+
+pub use crate::extract::{DocumentType, Extractor, Objects, Options, Reject, Summary};
+pub use crate::object::{OutAuthor, OutObject};
+pub use crate::sink::{ByTypeSink, Encoder, NdJsonEncoder, NdJsonSink, Sink};
@@ -1,187 +1,460 @@
+mod cli;
+mod output;
+
 use std::borrow::Cow;
-use std::fs::File;
-use std::io::Write as _;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Write as _};
 use std::path::Path;
-use std::{env, io};
+use std::process::ExitCode;
+use std::{fmt, io};
 
 use anyhow::Context;
-use csv::{ReaderBuilder, StringRecord};
-use flate2::bufread::GzDecoder;
-use heed::{EnvOpenOptions, types::Str};
-use serde::{Serialize, Deserialize};
-
-#[derive(Debug, Deserialize)]
-struct InAuthor<'a> {
-    #[serde(borrow)]
-    name: Cow<'a, str>,
+use clap::Parser;
+use open_library_extractor::extract::{author_object, book_object, resolve_redirects, work_object, BATCH_SIZE};
+#[cfg(feature = "meilisearch")]
+use open_library_extractor::meilisearch::IndexSettings;
+use open_library_extractor::model::Record;
+use open_library_extractor::store::{HeedStore, LookupStore, MemoryStore, StoreReader, Table};
+use open_library_extractor::{dump, pipeline, DocumentType, Encoder, Extractor, NdJsonEncoder, Options, Reject, Sink, Summary};
+
+use crate::cli::{Cli, Command};
+use crate::output::Outputs;
+
+/// The error of the strict mode, when a record can't be read or parsed.
+#[derive(Debug)]
+struct InvalidRecord {
+    line: u64,
+    error: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct InBook<'a> {
-    #[serde(borrow)]
-    publishers: Option<Vec<Cow<'a, str>>>,
+impl fmt::Display for InvalidRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid record at line {}: {}", self.line, self.error)
+    }
+}
 
-    #[serde(borrow)]
-    physical_format: Option<Cow<'a, str>>,
+impl std::error::Error for InvalidRecord {}
 
-    #[serde(borrow)]
-    subtitle: Option<Cow<'a, str>>,
+/// Counts the invalid records and writes them to a file, or stops at the first one.
+struct Rejects {
+    strict: bool,
+    count: usize,
+    writer: Option<io::BufWriter<File>>,
+}
 
-    #[serde(borrow)]
-    title: Cow<'a, str>,
+impl Rejects {
+    fn new(path: Option<&Path>, strict: bool) -> anyhow::Result<Rejects> {
+        let writer = match path {
+            Some(path) => {
+                let file = File::create(path).with_context(|| format!("while creating {:?}", path))?;
+                Some(io::BufWriter::new(file))
+            },
+            None => None,
+        };
+        Ok(Rejects { strict, count: 0, writer })
+    }
 
-    number_of_pages: Option<u64>,
+    fn push(&mut self, reject: Reject) -> anyhow::Result<()> {
+        if self.strict {
+            return Err(InvalidRecord { line: reject.line, error: reject.error }.into());
+        }
+        self.count += 1;
+        if let Some(writer) = self.writer.as_mut() {
+            serde_json::to_writer(&mut *writer, &reject)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
 
-    #[serde(borrow)]
-    publish_date: Option<Cow<'a, str>>,
+    fn finish(self) -> anyhow::Result<()> {
+        if self.count != 0 {
+            eprintln!("Rejected {} invalid records", self.count);
+        }
+        if let Some(writer) = self.writer {
+            writer.into_inner().map_err(|e| e.into_error())?;
+        }
+        Ok(())
+    }
+}
 
-    authors: Option<Vec<InAuthorKey<'a>>>,
+/// Returns the date of the dump, found in its file name (e.g. `ol_dump_2021-11-30.txt.gz`).
+fn dump_date_from_path(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let date = name.as_bytes().windows(10).find(|candidate| {
+        candidate.iter().enumerate().all(|(i, b)| match i {
+            4 | 7 => *b == b'-',
+            _ => b.is_ascii_digit(),
+        })
+    })?;
+    String::from_utf8(date.to_vec()).ok()
+}
 
-    identifiers: Option<InIdentifiers<'a>>,
+/// Converts the options of the command line, with the encoder of the exported fields.
+fn options(args: cli::DocumentArgs) -> (Options, NdJsonEncoder) {
+    let non_empty = |values: Vec<String>| Some(values).filter(|v| !v.is_empty());
+    let options = Options {
+        identifier_schemes: non_empty(args.identifiers).map(|s| s.into_iter().map(Cow::Owned).collect()),
+        nested_authors: args.nested_authors,
+        work_authors_fallback: args.work_authors_fallback,
+        types: args.types,
+    };
+    (options, NdJsonEncoder::new(non_empty(args.fields)))
+}
 
-    #[serde(borrow)]
-    subjects: Option<Vec<Cow<'a, str>>>,
+/// Writes the settings of a Meilisearch index for the exported documents,
+/// with the synonyms of the authors of the store if the output asks for them.
+#[cfg(feature = "meilisearch")]
+fn write_settings(store: &dyn LookupStore, output: &cli::OutputArgs, mut settings: IndexSettings) -> anyhow::Result<()> {
+    let path = match &output.meilisearch_settings {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    if output.author_synonyms {
+        store.read(&mut |reader| settings.add_author_synonyms(reader, output.max_author_synonyms))?;
+    }
+    let mut file = File::create(path).with_context(|| format!("while creating {:?}", path))?;
+    serde_json::to_writer_pretty(&mut file, &settings.to_json())?;
+    writeln!(file).map_err(Into::into)
 }
 
-#[derive(Debug, Deserialize)]
-struct InAuthorKey<'a> {
-    #[serde(borrow)]
-    key: Cow<'a, str>,
+/// Exports the editions kept in the store, then the authors and the works,
+/// the editions are the ones left by the extraction of a dump or all of them in an index.
+fn export_store<S: Sink>(
+    store: &dyn LookupStore,
+    extractor: &Extractor,
+    remaining: bool,
+    sink: &mut S,
+) -> anyhow::Result<Summary>
+{
+    let mut summary = Summary::default();
+
+    for kind in DocumentType::ALL.iter().copied().filter(|kind| extractor.options().includes(*kind)) {
+        match kind {
+            DocumentType::Book => {
+                let mut editions = 0;
+                store.read(&mut |reader| {
+                    editions = reader.len(Table::Editions)?;
+                    Ok(())
+                })?;
+                if remaining {
+                    eprintln!("Exporting the {} remaining books editions...", editions);
+                } else {
+                    eprintln!("Exporting the {} books editions of the index...", editions);
+                }
+            },
+            DocumentType::Author => eprintln!("Exporting the authors..."),
+            DocumentType::Work => eprintln!("Exporting the works..."),
+        }
+
+        summary.merge(extractor.export(kind, sink)?);
+    }
+
+    Ok(summary)
 }
 
-#[derive(Debug, Deserialize)]
-struct InIdentifiers<'a> {
-    #[serde(borrow)]
-    goodreads: Option<Vec<Cow<'a, str>>>,
+/// Reads the dump, exporting the books that can be resolved, then exports the rest of the store.
+fn extract_dump<S: Sink>(
+    store: &dyn LookupStore,
+    extractor: &Extractor,
+    dump: impl Read + Send,
+    mut rejects: Rejects,
+    sink: &mut S,
+) -> anyhow::Result<Summary>
+{
+    let mut summary = extractor.read_dump(dump, true, |reject| rejects.push(reject), sink)?;
+    rejects.finish()?;
+    summary.merge(export_store(store, extractor, true, sink)?);
+    Ok(summary)
 }
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "type")]
-#[serde(rename_all = "lowercase")]
-enum OutObject<'a> {
-    Book {
-        id: &'a str,
-        name: &'a str,
-
-        #[serde(skip_serializing_if = "Vec::is_empty")]
-        authors: Vec<&'a str>,
-
-        #[serde(skip_serializing_if = "Option::is_none")]
-        publish_year: Option<u32>,
-
-        #[serde(skip_serializing_if = "Option::is_none")]
-        number_of_pages: Option<u64>,
-
-        #[serde(skip_serializing_if = "Vec::is_empty")]
-        subjects: Vec<Cow<'a, str>>,
-
-        #[serde(skip_serializing_if = "Vec::is_empty")]
-        goodreads: Vec<Cow<'a, str>>,
-    },
-    Author {
-        id: &'a str,
-        name: &'a str,
-    },
+/// Reports the growth of the map of the LMDB store.
+fn report_growth(map_size: usize) {
+    eprintln!("The LMDB map is full, growing it to {} bytes", map_size);
 }
 
-fn open_file(path: impl AsRef<Path>) -> anyhow::Result<Box<dyn io::Read>> {
-    let path = path.as_ref();
-    let is_gzipped = path.extension().map_or(false, |e| e == "gz");
-    let file = File::open(&path).with_context(|| format!("while opening {:?}", path.display()))?;
-    if is_gzipped {
-        Ok(Box::new(GzDecoder::new(io::BufReader::new(file))))
-    } else {
-        Ok(Box::new(file))
+/// Opens the index built by the `index` subcommand and returns it with the date of its dump.
+fn open_index(index_dir: &Path) -> anyhow::Result<(HeedStore, String)> {
+    if !index_dir.join("data.mdb").exists() {
+        anyhow::bail!("{:?} doesn't contain an index, build it with the index subcommand", index_dir);
     }
-}
+    // The map is at least as big as the index already is.
+    let store = HeedStore::open(index_dir, 0)?;
 
-fn main() -> anyhow::Result<()> {
-    let file_path = env::args().nth(1).with_context(|| {
-        format!("usage: {} ol_dump_latest.txt.gz", env::args().nth(0).unwrap())
+    let mut dump_date = None;
+    store.read(&mut |reader| {
+        dump_date = reader.get(Table::Metadata, DUMP_DATE_KEY)?.map(ToOwned::to_owned);
+        Ok(())
     })?;
+    let dump_date = dump_date.with_context(|| format!("the index in {:?} is incomplete", index_dir))?;
 
-    let env = EnvOpenOptions::new()
-        .map_size(1024 * 1024 * 1024 * 10) // 10GB
-        .max_dbs(1)
-        .open(tempfile::tempdir()?)?;
-    let authors_ids_names = env.create_database::<Str, Str>(Some("authors-ids-names"))?;
+    Ok((store, dump_date))
+}
 
-    eprintln!("Extracting the authors ids and names");
+/// Prints the document of the key, following the redirects, returns `false` if it isn't found.
+fn lookup(
+    reader: &dyn StoreReader,
+    options: &Options,
+    encoder: &NdJsonEncoder,
+    key: &str,
+    output: &mut Vec<u8>,
+) -> anyhow::Result<bool>
+{
+    let key = match resolve_redirects(reader, Cow::Borrowed(key))? {
+        Some(key) => key,
+        None => return Ok(false),
+    };
+
+    // The documents are looked up one by one, the invalid ISBNs and redirect cycles are not reported.
+    let mut summary = Summary::default();
+    let object = if let Some(id) = key.strip_prefix("/books/") {
+        match reader.get(Table::Editions, id)? {
+            Some(json) => book_object(reader, options, id, serde_json::from_str(json)?, &mut summary)?,
+            None => return Ok(false),
+        }
+    } else if let Some(id) = key.strip_prefix("/authors/") {
+        match reader.get(Table::AuthorsIdsJsons, id)? {
+            Some(json) => author_object(id, serde_json::from_str(json)?),
+            None => return Ok(false),
+        }
+    } else if let Some(id) = key.strip_prefix("/works/") {
+        match reader.get(Table::WorksIdsJsons, id)? {
+            Some(json) => work_object(reader, options, id, serde_json::from_str(json)?, &mut summary)?,
+            None => return Ok(false),
+        }
+    } else if let Some(id) = key.strip_prefix("/languages/") {
+        match reader.get(Table::LanguagesIdsNames, id)? {
+            Some(name) => {
+                let language = serde_json::json!({ "type": "language", "id": id, "name": name });
+                serde_json::to_writer(&mut *output, &language)?;
+                output.push(b'\n');
+                return Ok(true);
+            },
+            None => return Ok(false),
+        }
+    } else {
+        return Ok(false);
+    };
 
-    let reader = open_file(&file_path)?;
-    let mut reader = ReaderBuilder::new().delimiter(b'\t').has_headers(true).from_reader(reader);
+    encoder.encode(output, object)?;
+    Ok(true)
+}
 
-    let mut wtxn = env.write_txn()?;
-    let mut record = StringRecord::new();
-    while reader.read_record(&mut record)? {
-        if let Some(author_id) = record[1].strip_prefix("/authors/") {
-            if let Ok(author) = serde_json::from_str::<InAuthor>(&record[4]) {
-                authors_ids_names.put(&mut wtxn, author_id, &author.name)?;
+/// The key of the date of the dump in the metadata of an index.
+const DUMP_DATE_KEY: &str = "dump-date";
+
+/// Some records couldn't be read or parsed, see the `validate` subcommand and the `--strict` flag.
+const EXIT_INVALID_RECORDS: u8 = 3;
+/// Some keys given to the `lookup` subcommand aren't in the index.
+const EXIT_NOT_FOUND: u8 = 4;
+
+fn run(command: Command) -> anyhow::Result<ExitCode> {
+    let mut summary = Summary::default();
+
+    match command {
+        Command::Extract { dump, read, store, documents, output } => {
+            let rejects = Rejects::new(read.rejects.as_deref(), read.strict)?;
+
+            // The temporary directory must outlive the store, it is removed when dropped.
+            let index_dir = match (&store.index_dir, store.in_memory) {
+                (_, true) => None,
+                (Some(dir), false) => Some(tempfile::tempdir_in(dir).with_context(|| format!("while creating a directory in {:?}", dir))?),
+                (None, false) => Some(tempfile::tempdir()?),
+            };
+
+            let lookup_store: Box<dyn LookupStore> = match &index_dir {
+                Some(dir) => Box::new(HeedStore::open(dir.path(), store.map_size)?.on_grow(report_growth)),
+                None => Box::new(MemoryStore::new()),
+            };
+
+            eprintln!("Extracting the authors, the works and the redirects and exporting the books editions...");
+
+            let (mut options, encoder) = options(documents);
+            let mut outputs = Outputs::create(&output, &mut options, encoder.clone(), read.threads)?;
+            #[cfg(feature = "meilisearch")]
+            let settings = IndexSettings::new(&options, &encoder.without_type(output.no_type_tag));
+            let extractor = Extractor::new(&*lookup_store, options).threads(read.threads);
+            let dump = dump::open(&dump)?;
+            summary.merge(match &mut outputs {
+                Outputs::Single(sink) => extract_dump(&*lookup_store, &extractor, dump, rejects, sink)?,
+                Outputs::ByType(sink) => extract_dump(&*lookup_store, &extractor, dump, rejects, sink)?,
+                #[cfg(feature = "columnar")]
+                Outputs::Columnar(sink) => extract_dump(&*lookup_store, &extractor, dump, rejects, sink)?,
+                #[cfg(feature = "sqlite")]
+                Outputs::Sqlite(sink) => extract_dump(&*lookup_store, &extractor, dump, rejects, sink)?,
+                Outputs::Csv(sink) => extract_dump(&*lookup_store, &extractor, dump, rejects, sink)?,
+                #[cfg(feature = "meilisearch")]
+                Outputs::Meilisearch(sink) => extract_dump(&*lookup_store, &extractor, dump, rejects, sink)?,
+            });
+            outputs.finish()?;
+            #[cfg(feature = "meilisearch")]
+            write_settings(&*lookup_store, &output, settings)?;
+        },
+        Command::Index { dump, index_dir, map_size, read } => {
+            let mut rejects = Rejects::new(read.rejects.as_deref(), read.strict)?;
+
+            if index_dir.join("data.mdb").exists() {
+                anyhow::bail!("{:?} already contains an index", index_dir);
             }
-        }
-    }
+            fs::create_dir_all(&index_dir).with_context(|| format!("while creating {:?}", index_dir))?;
+            let store = HeedStore::open(&index_dir, map_size)?.on_grow(report_growth);
+
+            eprintln!("Indexing the editions, the authors, the works, the redirects and the languages...");
+
+            // The options of the documents are only used when exporting the index.
+            let extractor = Extractor::new(&store, Options::default()).threads(read.threads);
+            summary.merge(extractor.index(dump::open(&dump)?, |reject| rejects.push(reject))?);
+            rejects.finish()?;
+
+            // The date of the dump is only written once the index is complete.
+            let dump_date = dump_date_from_path(&dump)
+                .or_else(|| summary.last_modified.as_deref().and_then(|date| date.get(..10)).map(ToOwned::to_owned))
+                .unwrap_or_default();
+            store.write(&[(Table::Metadata, DUMP_DATE_KEY.to_owned(), dump_date.clone())])?;
+            eprintln!("Indexed the dump of {} into {:?}", dump_date, index_dir);
+        },
+        Command::Export { index_dir, threads, documents, output } => {
+            let (store, dump_date) = open_index(&index_dir)?;
+            eprintln!("Exporting the index of the dump of {}...", dump_date);
+
+            let (mut options, encoder) = options(documents);
+            let mut outputs = Outputs::create(&output, &mut options, encoder.clone(), threads)?;
+            #[cfg(feature = "meilisearch")]
+            let settings = IndexSettings::new(&options, &encoder.without_type(output.no_type_tag));
+            let extractor = Extractor::new(&store, options).threads(threads);
+            summary.merge(match &mut outputs {
+                Outputs::Single(sink) => export_store(&store, &extractor, false, sink)?,
+                Outputs::ByType(sink) => export_store(&store, &extractor, false, sink)?,
+                #[cfg(feature = "columnar")]
+                Outputs::Columnar(sink) => export_store(&store, &extractor, false, sink)?,
+                #[cfg(feature = "sqlite")]
+                Outputs::Sqlite(sink) => export_store(&store, &extractor, false, sink)?,
+                Outputs::Csv(sink) => export_store(&store, &extractor, false, sink)?,
+                #[cfg(feature = "meilisearch")]
+                Outputs::Meilisearch(sink) => export_store(&store, &extractor, false, sink)?,
+            });
+            outputs.finish()?;
+            #[cfg(feature = "meilisearch")]
+            write_settings(&store, &output, settings)?;
+        },
+        Command::Stats { dump } => {
+            let mut counts = BTreeMap::new();
+            let (mut invalid, mut last_modified) = (0, None);
+            for result in dump::DumpReader::new(dump::open(&dump)?) {
+                match result {
+                    Ok(row) => {
+                        last_modified = last_modified.max(Some(row.last_modified));
+                        *counts.entry(row.kind).or_insert(0usize) += 1;
+                    },
+                    Err(dump::Error::Io(e)) => return Err(e.into()),
+                    Err(dump::Error::InvalidRow { .. }) => invalid += 1,
+                }
+            }
+
+            let mut counts: Vec<_> = counts.into_iter().collect();
+            counts.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
 
-    wtxn.commit()?;
-
-    eprintln!("Exporting the books editions as an ndJSON...");
-
-    let reader = open_file(&file_path)?;
-    let mut reader = ReaderBuilder::new().delimiter(b'\t').has_headers(true).from_reader(reader);
-    let mut writer = io::BufWriter::new(io::stdout());
-
-    let rtxn = env.read_txn()?;
-    let mut buffer = Vec::new();
-    let mut record = StringRecord::new();
-    while reader.read_record(&mut record)? {
-        if &record[0] == "/type/edition" {
-            if let Some(book_id) = record[1].strip_prefix("/books/") {
-                if let Ok(book) = serde_json::from_str::<InBook>(&record[4]) {
-                    let authors = book.authors.unwrap_or_default().into_iter()
-                        .flat_map(|InAuthorKey { key }| {
-                            let key = key.strip_prefix("/authors/")?;
-                            authors_ids_names.get(&rtxn, &key).map_err(Into::into).transpose()
-                        }).collect::<anyhow::Result<Vec<_>>>()?;
-
-                    let goodreads: Vec<_> = book.identifiers.into_iter()
-                        .flat_map(|InIdentifiers { goodreads }| goodreads)
-                        .flatten()
-                        .collect();
-
-                    let publish_year = book.publish_date.and_then(|s| {
-                        s.get(s.len() - 4..).and_then(|s| s.parse().ok())
-                    });
-
-                    let book = OutObject::Book {
-                        id: book_id,
-                        name: &book.title,
-                        authors: authors,
-                        publish_year,
-                        number_of_pages: book.number_of_pages,
-                        subjects: book.subjects.unwrap_or_default(),
-                        goodreads,
-                    };
-
-                    buffer.clear();
-                    serde_json::to_writer(&mut buffer, &book)?;
-                    buffer.push(b'\n');
-                    writer.write_all(&buffer)?;
+            let mut stdout = io::stdout().lock();
+            for (kind, count) in &counts {
+                writeln!(stdout, "{:>12} {}", count, kind)?;
+            }
+            writeln!(stdout, "{:>12} records", counts.iter().map(|(_, c)| c).sum::<usize>())?;
+            writeln!(stdout, "{:>12} invalid rows", invalid)?;
+            if let Some(date) = last_modified {
+                writeln!(stdout, "last modified on {}", date)?;
+            }
+        },
+        Command::Validate { dump, read } => {
+            let mut rejects = Rejects::new(read.rejects.as_deref(), read.strict)?;
+            let mut records = 0;
+
+            pipeline::run(
+                read.threads,
+                |send| {
+                    let mut rows = Vec::with_capacity(BATCH_SIZE);
+                    for result in dump::DumpReader::new(dump::open(&dump)?) {
+                        match result {
+                            Ok(row) => rows.push(Ok(row)),
+                            Err(dump::Error::Io(e)) => return Err(e.into()),
+                            Err(dump::Error::InvalidRow { line, error, raw }) => {
+                                rows.push(Err(Reject { line, error, record: raw }));
+                            },
+                        }
+                        if rows.len() == BATCH_SIZE {
+                            send(std::mem::replace(&mut rows, Vec::with_capacity(BATCH_SIZE)))?;
+                        }
+                    }
+                    if !rows.is_empty() {
+                        send(rows)?;
+                    }
+                    Ok(())
+                },
+                |rows| {
+                    let count = rows.len();
+                    let rejects: Vec<_> = rows.into_iter().filter_map(|row| match row {
+                        Ok(row) => Record::parse(&row.kind, &row.json).err().map(|e| Reject::new(&row, e)),
+                        Err(reject) => Some(reject),
+                    }).collect();
+                    Ok((count, rejects))
+                },
+                |(count, batch_rejects)| {
+                    records += count;
+                    batch_rejects.into_iter().try_for_each(|reject| rejects.push(reject))
+                },
+            )?;
+
+            let invalid = rejects.count;
+            rejects.finish()?;
+            eprintln!("Checked {} records, {} are invalid", records, invalid);
+            if invalid != 0 {
+                return Ok(ExitCode::from(EXIT_INVALID_RECORDS));
+            }
+        },
+        Command::Lookup { keys, index_dir, documents } => {
+            let (options, encoder) = options(documents);
+            let (store, _) = open_index(&index_dir)?;
+
+            let mut output = Vec::new();
+            let mut not_found = Vec::new();
+            store.read(&mut |reader| {
+                for key in &keys {
+                    if !lookup(reader, &options, &encoder, key, &mut output)? {
+                        not_found.push(key);
+                    }
                 }
+                Ok(())
+            })?;
+
+            io::stdout().write_all(&output)?;
+            if !not_found.is_empty() {
+                for key in not_found {
+                    eprintln!("{:?} not found", key);
+                }
+                return Ok(ExitCode::from(EXIT_NOT_FOUND));
             }
-        }
+        },
     }
 
-    eprintln!("Exporting the authors as an ndJSON...");
-
-    for result in authors_ids_names.iter(&rtxn)? {
-        let (id, name) = result?;
-        let author = OutObject::Author { id, name };
-        buffer.clear();
-        serde_json::to_writer(&mut buffer, &author)?;
-        buffer.push(b'\n');
-        writer.write_all(&buffer)?;
+    if summary.invalid_isbns != 0 {
+        eprintln!("Ignored {} invalid ISBNs", summary.invalid_isbns);
+    }
+    if summary.redirect_cycles != 0 {
+        eprintln!("Ignored {} references to redirect cycles", summary.redirect_cycles);
     }
 
-    writer.into_inner()?;
+    Ok(ExitCode::SUCCESS)
+}
 
-    Ok(())
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli.command) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("Error: {:?}", error);
+            match error.downcast_ref::<InvalidRecord>() {
+                Some(_) => ExitCode::from(EXIT_INVALID_RECORDS),
+                None => ExitCode::FAILURE,
+            }
+        },
+    }
 }
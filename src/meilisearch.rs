@@ -0,0 +1,541 @@
+//! Sends the documents to a Meilisearch index, over HTTP.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::extract::{DocumentType, Options};
+use crate::model::Author;
+use crate::sink::{NdJsonEncoder, Sink};
+use crate::store::{StoreReader, Table};
+
+/// The default maximum size of the payloads, below the 100MB limit of Meilisearch.
+pub const MAX_PAYLOAD_SIZE: usize = 32 * 1024 * 1024;
+
+/// A payload sent to Meilisearch, kept until its task succeeds to be sent again if it fails.
+struct Task {
+    uid: u64,
+    payload: Vec<u8>,
+    attempts: usize,
+}
+
+/// Sends the documents to a Meilisearch index, as ndJSON payloads of a limited size.
+///
+/// The payloads are sent while the previous ones are indexed, up to a number of pending tasks,
+/// then the sink waits for the oldest task. The requests that can't be sent and the tasks
+/// that fail are retried, with an increasing delay.
+pub struct MeilisearchSink {
+    agent: ureq::Agent,
+    url: String,
+    index: String,
+    api_key: Option<String>,
+    primary_key: String,
+    encoder: NdJsonEncoder,
+    max_payload_size: usize,
+    max_pending_tasks: usize,
+    retries: usize,
+    poll_interval: Duration,
+    /// The documents that are not sent yet.
+    payload: Vec<u8>,
+    pending: VecDeque<Task>,
+}
+
+impl MeilisearchSink {
+    /// Sends the documents to the index of the Meilisearch instance at this url, e.g. `http://localhost:7700`.
+    pub fn new(url: &str, index: &str, encoder: NdJsonEncoder) -> MeilisearchSink {
+        MeilisearchSink {
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(300)).build(),
+            url: url.trim_end_matches('/').to_owned(),
+            index: index.to_owned(),
+            api_key: None,
+            primary_key: "id".to_owned(),
+            encoder,
+            max_payload_size: MAX_PAYLOAD_SIZE,
+            max_pending_tasks: 4,
+            retries: 3,
+            poll_interval: Duration::from_millis(500),
+            payload: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// The key sent as a bearer token, e.g. the master key.
+    pub fn api_key(mut self, api_key: Option<String>) -> MeilisearchSink {
+        self.api_key = api_key;
+        self
+    }
+
+    /// The primary key of the index, `id` by default.
+    pub fn primary_key(mut self, primary_key: &str) -> MeilisearchSink {
+        self.primary_key = primary_key.to_owned();
+        self
+    }
+
+    /// The maximum number of bytes of documents sent at once, a bigger document is sent alone.
+    pub fn max_payload_size(mut self, size: usize) -> MeilisearchSink {
+        self.max_payload_size = size;
+        self
+    }
+
+    /// The number of tasks that can be pending before waiting for the oldest one.
+    pub fn max_pending_tasks(mut self, count: usize) -> MeilisearchSink {
+        self.max_pending_tasks = count.max(1);
+        self
+    }
+
+    /// The number of times a request or a task is retried before giving up.
+    pub fn retries(mut self, retries: usize) -> MeilisearchSink {
+        self.retries = retries;
+        self
+    }
+
+    /// The delay between two checks of the status of a task, also the first delay before a retry.
+    pub fn poll_interval(mut self, interval: Duration) -> MeilisearchSink {
+        self.poll_interval = interval;
+        self
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = self.agent.request(method, &format!("{}{}", self.url, path));
+        match &self.api_key {
+            Some(key) => request.set("Authorization", &format!("Bearer {}", key)),
+            None => request,
+        }
+    }
+
+    /// Sends a request, retrying when Meilisearch can't be reached or is overloaded.
+    fn send(&self, method: &str, path: &str, body: &[u8]) -> anyhow::Result<Value> {
+        let mut attempt = 0;
+        loop {
+            let request = self.request(method, path).set("Content-Type", "application/x-ndjson");
+            let result = if method == "GET" { request.call() } else { request.send_bytes(body) };
+            let error = match result {
+                Ok(response) => return serde_json::from_str(&response.into_string()?).map_err(Into::into),
+                Err(ureq::Error::Status(status, response)) if status == 429 || status >= 500 => {
+                    format!("{} {}", status, response.into_string().unwrap_or_default())
+                },
+                Err(ureq::Error::Status(status, response)) => {
+                    let body = response.into_string().unwrap_or_default();
+                    anyhow::bail!("{} {} failed with {}: {}", method, path, status, body);
+                },
+                Err(error @ ureq::Error::Transport(_)) => error.to_string(),
+            };
+
+            if attempt >= self.retries {
+                anyhow::bail!("{} {} failed after {} attempts: {}", method, path, attempt + 1, error);
+            }
+            thread::sleep(self.poll_interval * 2u32.saturating_pow(attempt as u32));
+            attempt += 1;
+        }
+    }
+
+    /// Sends the documents of the payload, it becomes a pending task.
+    fn send_payload(&mut self, payload: Vec<u8>, attempts: usize) -> anyhow::Result<()> {
+        let path = format!("/indexes/{}/documents?primaryKey={}", self.index, self.primary_key);
+        let response = self.send("POST", &path, &payload)?;
+        let uid = match response.get("taskUid").or_else(|| response.get("uid")).and_then(Value::as_u64) {
+            Some(uid) => uid,
+            None => anyhow::bail!("unexpected response of Meilisearch: {}", response),
+        };
+        self.pending.push_back(Task { uid, payload, attempts });
+        Ok(())
+    }
+
+    /// Sends the documents that are not sent yet, waiting for a task if there are too many pending.
+    fn flush_payload(&mut self) -> anyhow::Result<()> {
+        if !self.payload.is_empty() {
+            let payload = std::mem::take(&mut self.payload);
+            self.send_payload(payload, 0)?;
+        }
+        while self.pending.len() > self.max_pending_tasks {
+            self.wait_oldest_task()?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the oldest pending task, and sends its documents again if it failed.
+    fn wait_oldest_task(&mut self) -> anyhow::Result<()> {
+        let task = match self.pending.pop_front() {
+            Some(task) => task,
+            None => return Ok(()),
+        };
+
+        loop {
+            let response = self.send("GET", &format!("/tasks/{}", task.uid), &[])?;
+            match response.get("status").and_then(Value::as_str) {
+                Some("succeeded") => return Ok(()),
+                Some("enqueued") | Some("processing") => thread::sleep(self.poll_interval),
+                status => {
+                    let error = response.get("error").cloned().unwrap_or(Value::Null);
+                    if task.attempts >= self.retries {
+                        anyhow::bail!("the task {} is {} after {} attempts: {}", task.uid, status.unwrap_or("unknown"), task.attempts + 1, error);
+                    }
+                    thread::sleep(self.poll_interval * 2u32.saturating_pow(task.attempts as u32));
+                    // The documents are sent again, as the newest task.
+                    return self.send_payload(task.payload, task.attempts + 1);
+                },
+            }
+        }
+    }
+}
+
+impl Sink for MeilisearchSink {
+    type Encoder = NdJsonEncoder;
+
+    fn encoder(&self) -> NdJsonEncoder {
+        self.encoder.clone()
+    }
+
+    fn write(&mut self, batch: Vec<u8>) -> anyhow::Result<()> {
+        for line in batch.split_inclusive(|b| *b == b'\n') {
+            if !self.payload.is_empty() && self.payload.len() + line.len() > self.max_payload_size {
+                self.flush_payload()?;
+            }
+            self.payload.extend_from_slice(line);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.flush_payload()?;
+        while !self.pending.is_empty() {
+            self.wait_oldest_task()?;
+        }
+        Ok(())
+    }
+}
+
+/// The settings of a Meilisearch index that match the exported documents and their fields.
+#[derive(Debug, Clone)]
+pub struct IndexSettings {
+    types: Vec<DocumentType>,
+    fields: Option<Vec<String>>,
+    without_type: bool,
+    /// The names of the authors and their alternate names, each one with the others of the same author.
+    synonyms: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl IndexSettings {
+    /// The settings of the types of documents of the options, with the fields kept by the encoder.
+    pub fn new(options: &Options, encoder: &NdJsonEncoder) -> IndexSettings {
+        let types = DocumentType::ALL.iter().copied().filter(|kind| options.includes(*kind)).collect();
+        IndexSettings {
+            types,
+            fields: encoder.fields.clone(),
+            without_type: encoder.without_type,
+            synonyms: BTreeMap::new(),
+        }
+    }
+
+    /// Whether a field of some of these types of documents is exported.
+    fn exports(&self, field: &str, types: &[DocumentType]) -> bool {
+        let fields_include = match field {
+            "id" => true,
+            "type" => !self.without_type,
+            field => self.fields.as_ref().is_none_or(|fields| fields.iter().any(|f| f == field)),
+        };
+        fields_include && self.types.iter().any(|kind| types.contains(kind))
+    }
+
+    /// Makes the names of the authors of the store and their alternate names synonyms,
+    /// if the names of the authors are searchable.
+    ///
+    /// The names shared by several authors are left out, so that homonyms aren't merged, and the authors are added
+    /// in the order of their ids until `max_synonyms` names would be exceeded.
+    pub fn add_author_synonyms(&mut self, reader: &dyn StoreReader, max_synonyms: usize) -> anyhow::Result<()> {
+        let searchable_names = self.exports("authors", &[DocumentType::Book, DocumentType::Work])
+            || self.exports("name", &[DocumentType::Author]);
+        if !searchable_names {
+            return Ok(());
+        }
+
+        // The names of the authors with alternate names, and the author of each name or `None` if it is shared.
+        let mut authors = Vec::new();
+        let mut owners: HashMap<String, Option<String>> = HashMap::new();
+        let mut count = 0;
+        for result in reader.iter(Table::AuthorsIdsJsons)? {
+            let (id, json) = result?;
+            let names = author_names(json)?;
+            if names.len() < 2 {
+                continue;
+            }
+            if count + names.len() > max_synonyms {
+                break;
+            }
+            count += names.len();
+            for name in &names {
+                owners.insert(name.clone(), Some(id.to_owned()));
+            }
+            authors.push(names);
+        }
+
+        // Another pass to find the names that are also the names of other authors.
+        if !owners.is_empty() {
+            for result in reader.iter(Table::AuthorsIdsJsons)? {
+                let (id, json) = result?;
+                for name in author_names(json)? {
+                    if let Some(owner) = owners.get_mut(&name) {
+                        if owner.as_deref() != Some(id) {
+                            *owner = None;
+                        }
+                    }
+                }
+            }
+        }
+
+        for mut names in authors {
+            names.retain(|name| owners[name].is_some());
+            for name in &names {
+                let others = names.iter().filter(|other| *other != name).cloned();
+                self.synonyms.entry(name.clone()).or_default().extend(others);
+            }
+        }
+        self.synonyms.retain(|_, others| !others.is_empty());
+        Ok(())
+    }
+
+    /// The settings, in the format of the `/indexes/{index}/settings` route.
+    pub fn to_json(&self) -> Value {
+        use DocumentType::{Author, Book, Work};
+
+        let attributes = |candidates: &[(&str, &[DocumentType])]| -> Vec<String> {
+            candidates.iter().filter(|(field, types)| self.exports(field, types)).map(|(field, _)| field.to_string()).collect()
+        };
+
+        // The most important attributes first.
+        let searchable = attributes(&[
+            ("name", &[Book, Author, Work]),
+            ("subtitle", &[Book]),
+            ("authors", &[Book, Work]),
+            ("alternate_names", &[Author]),
+            ("subjects", &[Book, Work]),
+        ]);
+        let filterable = attributes(&[
+            ("type", &[Book, Author, Work]),
+            ("publish_year", &[Book]),
+            ("number_of_pages", &[Book]),
+            ("languages", &[Book]),
+            ("format", &[Book]),
+        ]);
+        let sortable = attributes(&[("publish_year", &[Book])]);
+
+        let mut ranking_rules = vec!["words", "typo", "proximity", "attribute", "sort", "exactness"];
+        if !sortable.is_empty() {
+            // The most recent editions first, when everything else is equal.
+            ranking_rules.push("publish_year:desc");
+        }
+
+        json!({
+            "searchableAttributes": searchable,
+            "filterableAttributes": filterable,
+            "sortableAttributes": sortable,
+            "rankingRules": ranking_rules,
+            "synonyms": self.synonyms,
+        })
+    }
+}
+
+/// The distinct non-empty names of an author, with its alternate names.
+fn author_names(json: &str) -> anyhow::Result<Vec<String>> {
+    let author: Author = serde_json::from_str(json)?;
+    let mut names: Vec<_> = author.alternate_names.unwrap_or_default();
+    names.push(author.name);
+    let mut names: Vec<String> =
+        names.into_iter().filter(|name| !name.trim().is_empty()).map(|name| name.into_owned()).collect();
+    names.sort_unstable();
+    names.dedup();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::store::{LookupStore, MemoryStore};
+    use crate::testing::extract_sample;
+
+    /// The requests received by the mock of Meilisearch: the method, the url, the authorization and the body.
+    type Requests = Arc<Mutex<Vec<(String, String, Option<String>, String)>>>;
+
+    /// Starts a mock of Meilisearch, that gives a response to each request with the given function.
+    fn mock_server(respond: impl Fn(&str, &str, usize) -> (u16, String) + Send + 'static) -> (String, Requests, Arc<tiny_http::Server>) {
+        let server = Arc::new(tiny_http::Server::http("127.0.0.1:0").unwrap());
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let requests = Requests::default();
+
+        let (thread_server, thread_requests) = (server.clone(), requests.clone());
+        thread::spawn(move || {
+            for mut request in thread_server.incoming_requests() {
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                let authorization = request.headers().iter()
+                    .find(|header| header.field.equiv("Authorization"))
+                    .map(|header| header.value.to_string());
+                let (method, url) = (request.method().to_string(), request.url().to_owned());
+
+                let mut requests = thread_requests.lock().unwrap();
+                let (status, response) = respond(&method, &url, requests.len());
+                requests.push((method, url, authorization, body));
+                drop(requests);
+                request.respond(tiny_http::Response::from_string(response).with_status_code(status)).unwrap();
+            }
+        });
+
+        (url, requests, server)
+    }
+
+    fn extract(sink: &mut MeilisearchSink) -> anyhow::Result<()> {
+        extract_sample(Options::default(), sink).map(drop)
+    }
+
+    #[test]
+    fn send_limited_payloads() {
+        let (url, requests, server) = mock_server(|method, url, index| match method {
+            "POST" => (202, format!(r#"{{"taskUid": {}, "status": "enqueued"}}"#, index)),
+            _ => {
+                let uid = url.trim_start_matches("/tasks/");
+                (200, format!(r#"{{"uid": {}, "status": "succeeded"}}"#, uid))
+            },
+        });
+
+        let mut sink = MeilisearchSink::new(&url, "books", NdJsonEncoder::default())
+            .api_key(Some("secret".to_owned()))
+            .max_payload_size(10_000)
+            .max_pending_tasks(2)
+            .poll_interval(Duration::from_millis(1));
+        extract(&mut sink).unwrap();
+        server.unblock();
+
+        let requests = requests.lock().unwrap();
+        let payloads: Vec<_> = requests.iter().filter(|(method, ..)| method == "POST").collect();
+        assert!(payloads.len() > 5);
+        for (_, url, authorization, body) in &payloads {
+            assert_eq!(url, "/indexes/books/documents?primaryKey=id");
+            assert_eq!(authorization.as_deref(), Some("Bearer secret"));
+            assert!(body.len() <= 10_000);
+            assert!(body.ends_with('\n'));
+        }
+        let documents = payloads.iter().flat_map(|(.., body)| body.lines()).count();
+        assert_eq!(documents, 197);
+
+        // Every task has been waited for.
+        let polls = requests.iter().filter(|(method, ..)| method == "GET").count();
+        assert_eq!(polls, payloads.len());
+    }
+
+    #[test]
+    fn retry_the_failed_requests_and_tasks() {
+        // The first request fails, the first task fails and the second is processing when polled first.
+        let (url, requests, server) = mock_server(|method, url, index| match (method, index) {
+            ("POST", 0) => (503, r#"{"message": "unavailable"}"#.to_owned()),
+            ("POST", _) => (202, format!(r#"{{"taskUid": {}}}"#, index)),
+            ("GET", _) => match url.trim_start_matches("/tasks/") {
+                "1" => (200, r#"{"status": "failed", "error": {"message": "internal"}}"#.to_owned()),
+                _ if index == 4 => (200, r#"{"status": "processing"}"#.to_owned()),
+                _ => (200, r#"{"status": "succeeded"}"#.to_owned()),
+            },
+            _ => (404, String::new()),
+        });
+
+        let mut sink = MeilisearchSink::new(&url, "books", NdJsonEncoder::default())
+            .max_payload_size(MAX_PAYLOAD_SIZE)
+            .poll_interval(Duration::from_millis(1));
+        extract(&mut sink).unwrap();
+        server.unblock();
+
+        let requests = requests.lock().unwrap();
+        let methods: Vec<_> = requests.iter().map(|(method, url, ..)| format!("{} {}", method, url)).collect();
+        assert_eq!(methods, [
+            "POST /indexes/books/documents?primaryKey=id",
+            "POST /indexes/books/documents?primaryKey=id",
+            "GET /tasks/1",
+            "POST /indexes/books/documents?primaryKey=id",
+            "GET /tasks/3",
+            "GET /tasks/3",
+        ]);
+        // The same documents are sent each time.
+        assert_eq!(requests[0].3.lines().count(), 197);
+        assert_eq!(requests[0].3, requests[3].3);
+    }
+
+    #[test]
+    fn give_up_after_the_retries() {
+        let (url, requests, server) = mock_server(|_, _, _| (500, String::new()));
+
+        let mut sink = MeilisearchSink::new(&url, "books", NdJsonEncoder::default())
+            .retries(2)
+            .poll_interval(Duration::from_millis(1));
+        let error = extract(&mut sink).unwrap_err();
+        server.unblock();
+
+        assert!(error.to_string().contains("after 3 attempts"), "{}", error);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn settings_of_the_exported_fields() {
+        let options = Options { types: vec![DocumentType::Book], ..Options::default() };
+        let fields = ["name", "authors", "publish_year", "format"].iter().map(|f| f.to_string()).collect();
+        let encoder = NdJsonEncoder::new(Some(fields)).without_type(true);
+        let settings = IndexSettings::new(&options, &encoder).to_json();
+
+        assert_eq!(settings["searchableAttributes"], json!(["name", "authors"]));
+        assert_eq!(settings["filterableAttributes"], json!(["publish_year", "format"]));
+        assert_eq!(settings["sortableAttributes"], json!(["publish_year"]));
+        assert_eq!(settings["rankingRules"].as_array().unwrap().last().unwrap(), "publish_year:desc");
+
+        let settings = IndexSettings::new(&Options::default(), &NdJsonEncoder::default()).to_json();
+        assert_eq!(settings["searchableAttributes"], json!(["name", "subtitle", "authors", "alternate_names", "subjects"]));
+        assert_eq!(
+            settings["filterableAttributes"],
+            json!(["type", "publish_year", "number_of_pages", "languages", "format"]),
+        );
+    }
+
+    #[test]
+    fn synonyms_of_the_alternate_names() {
+        let store = MemoryStore::new();
+        let twain = r#"{"name": "Mark Twain", "alternate_names": ["Samuel Clemens", "Mark Twain", "S. L. Clemens"]}"#;
+        store.write(&[
+            (Table::AuthorsIdsJsons, "OL18319A".to_owned(), twain.to_owned()),
+            (Table::AuthorsIdsJsons, "OL1A".to_owned(), r#"{"name": "Someone"}"#.to_owned()),
+        ]).unwrap();
+
+        let mut settings = IndexSettings::new(&Options::default(), &NdJsonEncoder::default());
+        store.read(&mut |reader| settings.add_author_synonyms(reader, 100)).unwrap();
+        let synonyms = &settings.to_json()["synonyms"];
+
+        assert_eq!(synonyms.as_object().unwrap().len(), 3);
+        assert_eq!(synonyms["Mark Twain"], json!(["S. L. Clemens", "Samuel Clemens"]));
+        assert_eq!(synonyms["Samuel Clemens"], json!(["Mark Twain", "S. L. Clemens"]));
+
+        // The names of several authors are left out.
+        let smith = r#"{"name": "John Smith", "alternate_names": ["J. Smith", "Smith, John"]}"#;
+        let other_smith = r#"{"name": "J. Smith", "alternate_names": ["Jane Smith"]}"#;
+        store.write(&[
+            (Table::AuthorsIdsJsons, "OL2A".to_owned(), smith.to_owned()),
+            (Table::AuthorsIdsJsons, "OL3A".to_owned(), other_smith.to_owned()),
+            (Table::AuthorsIdsJsons, "OL4A".to_owned(), r#"{"name": "Smith, John"}"#.to_owned()),
+        ]).unwrap();
+        let mut settings = IndexSettings::new(&Options::default(), &NdJsonEncoder::default());
+        store.read(&mut |reader| settings.add_author_synonyms(reader, 100)).unwrap();
+        let synonyms = &settings.to_json()["synonyms"];
+        assert_eq!(synonyms.as_object().unwrap().len(), 3);
+        assert_eq!(synonyms.get("John Smith"), None);
+        assert_eq!(synonyms.get("J. Smith"), None);
+
+        // The authors that would exceed the maximum number of names are left out.
+        let mut settings = IndexSettings::new(&Options::default(), &NdJsonEncoder::default());
+        store.read(&mut |reader| settings.add_author_synonyms(reader, 2)).unwrap();
+        assert_eq!(settings.to_json()["synonyms"], json!({}));
+
+        // Without the names of the authors there is nothing to match.
+        let options = Options { types: vec![DocumentType::Book], ..Options::default() };
+        let mut settings = IndexSettings::new(&options, &NdJsonEncoder::new(Some(vec!["name".to_owned()])));
+        store.read(&mut |reader| settings.add_author_synonyms(reader, 100)).unwrap();
+        assert_eq!(settings.to_json()["synonyms"], json!({}));
+    }
+}
@@ -0,0 +1,360 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer};
+
+/// An author, a `/type/author` record.
+///
+/// Only the name is required, the other fields are ignored when they are invalid.
+#[derive(Debug, Deserialize)]
+pub struct Author<'a> {
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+
+    #[serde(borrow, default, deserialize_with = "lenient")]
+    pub personal_name: Option<Cow<'a, str>>,
+
+    #[serde(borrow, default, deserialize_with = "lenient")]
+    pub alternate_names: Option<Vec<Cow<'a, str>>>,
+
+    #[serde(borrow, default, deserialize_with = "lenient")]
+    pub birth_date: Option<Cow<'a, str>>,
+
+    #[serde(borrow, default, deserialize_with = "lenient")]
+    pub death_date: Option<Cow<'a, str>>,
+
+    #[serde(borrow, default, deserialize_with = "lenient")]
+    pub bio: Option<Text<'a>>,
+
+    #[serde(default, deserialize_with = "lenient")]
+    pub photos: Option<Vec<i64>>,
+
+    /// The identifiers of the author in other catalogs, e.g. `wikidata`, `viaf` or `isni`.
+    #[serde(borrow, default, deserialize_with = "lenient")]
+    pub remote_ids: Option<BTreeMap<Cow<'a, str>, Cow<'a, str>>>,
+}
+
+/// An edition of a book, a `/type/edition` record.
+#[derive(Debug, Deserialize)]
+pub struct Edition<'a> {
+    #[serde(borrow)]
+    pub publishers: Option<Vec<Cow<'a, str>>>,
+
+    #[serde(borrow)]
+    pub physical_format: Option<Cow<'a, str>>,
+
+    #[serde(borrow)]
+    pub subtitle: Option<Cow<'a, str>>,
+
+    #[serde(borrow)]
+    pub title: Cow<'a, str>,
+
+    pub number_of_pages: Option<u64>,
+
+    #[serde(borrow)]
+    pub publish_date: Option<Cow<'a, str>>,
+
+    pub authors: Option<Vec<AuthorKey<'a>>>,
+
+    #[serde(borrow, default, deserialize_with = "identifiers")]
+    pub identifiers: Option<Identifiers<'a>>,
+
+    #[serde(borrow)]
+    pub oclc_numbers: Option<Vec<Cow<'a, str>>>,
+
+    #[serde(borrow)]
+    pub lccn: Option<Vec<Cow<'a, str>>>,
+
+    #[serde(borrow)]
+    pub ocaid: Option<Cow<'a, str>>,
+
+    #[serde(borrow)]
+    pub isbn_10: Option<Vec<Cow<'a, str>>>,
+
+    #[serde(borrow)]
+    pub isbn_13: Option<Vec<Cow<'a, str>>>,
+
+    #[serde(borrow)]
+    pub subjects: Option<Vec<Cow<'a, str>>>,
+
+    pub languages: Option<Vec<LanguageKey<'a>>>,
+
+    pub works: Option<Vec<WorkKey<'a>>>,
+}
+
+/// A work, the set of the editions of a book, a `/type/work` record.
+///
+/// Only the title is required, the other fields and the entries of the authors are ignored when they are invalid.
+#[derive(Debug, Deserialize)]
+pub struct Work<'a> {
+    #[serde(borrow)]
+    pub title: Cow<'a, str>,
+
+    #[serde(borrow, default, deserialize_with = "lenient")]
+    pub subjects: Option<Vec<Cow<'a, str>>>,
+
+    #[serde(borrow, default, deserialize_with = "lenient")]
+    pub description: Option<Text<'a>>,
+
+    #[serde(borrow, default, deserialize_with = "lenient")]
+    pub first_publish_date: Option<Cow<'a, str>>,
+
+    #[serde(borrow, default, deserialize_with = "work_authors")]
+    pub authors: Option<Vec<WorkAuthor<'a>>>,
+}
+
+/// The works reference their authors through a role object,
+/// e.g. `{ "author": { "key": "/authors/OL1A" }, "type": { "key": "/type/author_role" } }`,
+/// some older works directly use the `{ "key": "/authors/OL1A" }` form of the editions.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum WorkAuthor<'a> {
+    Role {
+        #[serde(borrow)]
+        author: AuthorKey<'a>,
+    },
+    Key(#[serde(borrow)] AuthorKey<'a>),
+}
+
+impl<'a> WorkAuthor<'a> {
+    pub fn key(&self) -> &str {
+        match self {
+            WorkAuthor::Role { author } => &author.key,
+            WorkAuthor::Key(author) => &author.key,
+        }
+    }
+
+    pub fn into_key(self) -> Cow<'a, str> {
+        match self {
+            WorkAuthor::Role { author } => author.key,
+            WorkAuthor::Key(author) => author.key,
+        }
+    }
+}
+
+/// Reads the authors of a work, ignoring the entries that aren't authors instead of rejecting the whole work.
+fn work_authors<'de: 'a, 'a, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<WorkAuthor<'a>>>, D::Error> {
+    let authors = match Lenient::<Vec<Lenient<WorkAuthor<'a>>>>::deserialize(deserializer)? {
+        Lenient::Valid(authors) => authors,
+        Lenient::Invalid(_) => return Ok(None),
+    };
+
+    let authors = authors.into_iter().filter_map(|author| match author {
+        Lenient::Valid(author) => Some(author),
+        Lenient::Invalid(_) => None,
+    });
+    Ok(Some(authors.collect()))
+}
+
+/// A text field can either be a plain string or a `/type/text` object.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Text<'a> {
+    Plain(#[serde(borrow)] Cow<'a, str>),
+    Typed {
+        #[serde(borrow)]
+        value: Cow<'a, str>,
+    },
+}
+
+impl<'a> Text<'a> {
+    pub fn into_inner(self) -> Cow<'a, str> {
+        match self {
+            Text::Plain(text) => text,
+            Text::Typed { value } => value,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorKey<'a> {
+    #[serde(borrow)]
+    pub key: Cow<'a, str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkKey<'a> {
+    #[serde(borrow)]
+    pub key: Cow<'a, str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LanguageKey<'a> {
+    #[serde(borrow)]
+    pub key: Cow<'a, str>,
+}
+
+/// The identifiers of an edition indexed by scheme (e.g. `goodreads`, `librarything`, `amazon`).
+pub type Identifiers<'a> = BTreeMap<Cow<'a, str>, Vec<Cow<'a, str>>>;
+
+/// A value that is ignored when it doesn't have the expected shape.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Lenient<T> {
+    Valid(T),
+    Invalid(IgnoredAny),
+}
+
+/// Reads an optional field, as `None` when it doesn't have the expected shape,
+/// instead of rejecting the whole record.
+fn lenient<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+    match Lenient::deserialize(deserializer)? {
+        Lenient::Valid(value) => Ok(Some(value)),
+        Lenient::Invalid(_) => Ok(None),
+    }
+}
+
+/// An identifier, some of them are numbers in the dump.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Identifier<'a> {
+    Text(#[serde(borrow)] Cow<'a, str>),
+    Number(u64),
+}
+
+impl<'a> Identifier<'a> {
+    fn into_text(self) -> Cow<'a, str> {
+        match self {
+            Identifier::Text(text) => text,
+            Identifier::Number(number) => Cow::Owned(number.to_string()),
+        }
+    }
+}
+
+/// The identifiers of a scheme, either a single one or a list of them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SchemeIdentifiers<'a> {
+    One(#[serde(borrow)] Identifier<'a>),
+    Many(#[serde(borrow)] Vec<Lenient<Identifier<'a>>>),
+}
+
+/// Reads the identifiers of an edition, ignoring the values that aren't identifiers
+/// instead of rejecting the whole edition.
+fn identifiers<'de: 'a, 'a, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Identifiers<'a>>, D::Error> {
+    let schemes = match Lenient::<BTreeMap<Cow<'a, str>, Lenient<SchemeIdentifiers<'a>>>>::deserialize(deserializer)? {
+        Lenient::Valid(schemes) => schemes,
+        Lenient::Invalid(_) => return Ok(None),
+    };
+
+    let identifiers = schemes.into_iter().filter_map(|(scheme, values)| {
+        let values = match values {
+            Lenient::Valid(SchemeIdentifiers::One(value)) => vec![value.into_text()],
+            Lenient::Valid(SchemeIdentifiers::Many(values)) => values.into_iter().filter_map(|value| match value {
+                Lenient::Valid(value) => Some(value.into_text()),
+                Lenient::Invalid(_) => None,
+            }).collect(),
+            Lenient::Invalid(_) => return None,
+        };
+        Some((scheme, values))
+    });
+    Ok(Some(identifiers.collect()))
+}
+
+/// A language, a `/type/language` record.
+#[derive(Debug, Deserialize)]
+pub struct Language<'a> {
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+}
+
+/// A record that moved to another key, a `/type/redirect` record.
+#[derive(Debug, Deserialize)]
+pub struct Redirect<'a> {
+    #[serde(borrow)]
+    pub location: Cow<'a, str>,
+}
+
+/// A record of the dump, parsed according to its type.
+#[derive(Debug)]
+pub enum Record<'a> {
+    Edition(Edition<'a>),
+    Author(Author<'a>),
+    Work(Work<'a>),
+    Redirect(Redirect<'a>),
+    Language(Language<'a>),
+    /// A type of record that isn't extracted, e.g. `/type/page`.
+    Other,
+}
+
+impl<'a> Record<'a> {
+    /// Parses the JSON column of a record of the given type (e.g. `/type/edition`).
+    pub fn parse(kind: &str, json: &'a str) -> serde_json::Result<Record<'a>> {
+        match kind {
+            "/type/edition" => serde_json::from_str(json).map(Record::Edition),
+            "/type/author" => serde_json::from_str(json).map(Record::Author),
+            "/type/work" => serde_json::from_str(json).map(Record::Work),
+            "/type/redirect" => serde_json::from_str(json).map(Record::Redirect),
+            "/type/language" => serde_json::from_str(json).map(Record::Language),
+            _ => Ok(Record::Other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignore_the_invalid_author_fields() {
+        let json = r#"{
+            "name": "A",
+            "birth_date": "1900",
+            "death_date": 1950,
+            "bio": {"type": "/type/text"},
+            "photos": "none",
+            "alternate_names": ["B", "C"],
+            "remote_ids": {"viaf": 123}
+        }"#;
+        let author: Author = serde_json::from_str(json).unwrap();
+        assert_eq!(author.name, "A");
+        assert_eq!(author.birth_date.as_deref(), Some("1900"));
+        assert_eq!(author.alternate_names.unwrap(), ["B", "C"]);
+        assert!(author.death_date.is_none());
+        assert!(author.bio.is_none());
+        assert!(author.photos.is_none());
+        assert!(author.remote_ids.is_none());
+
+        assert!(serde_json::from_str::<Author>(r#"{"name": 1}"#).is_err());
+    }
+
+    #[test]
+    fn ignore_the_invalid_identifiers() {
+        let json = r#"{
+            "title": "A",
+            "identifiers": {"goodreads": ["1", 2, {"x": 3}], "wikidata": "Q1", "amazon": null, "librarything": {}}
+        }"#;
+        let edition: Edition = serde_json::from_str(json).unwrap();
+        let identifiers = edition.identifiers.unwrap();
+        assert_eq!(identifiers.len(), 2);
+        assert_eq!(identifiers["goodreads"], ["1", "2"]);
+        assert_eq!(identifiers["wikidata"], ["Q1"]);
+
+        let edition: Edition = serde_json::from_str(r#"{"title": "A", "identifiers": ["Q1"]}"#).unwrap();
+        assert!(edition.identifiers.is_none());
+        let edition: Edition = serde_json::from_str(r#"{"title": "A"}"#).unwrap();
+        assert!(edition.identifiers.is_none());
+    }
+
+    #[test]
+    fn ignore_the_invalid_work_authors() {
+        let json = r#"{
+            "title": "A",
+            "subjects": "B",
+            "authors": [
+                {"author": {"key": "/authors/OL1A"}, "type": {"key": "/type/author_role"}},
+                {"type": {"key": "/type/author_role"}, "role": "illustrator"},
+                {"author": "/authors/OL2A"},
+                {"key": "/authors/OL3A"}
+            ]
+        }"#;
+        let work: Work = serde_json::from_str(json).unwrap();
+        let keys: Vec<_> = work.authors.unwrap().iter().map(|author| author.key().to_owned()).collect();
+        assert_eq!(keys, ["/authors/OL1A", "/authors/OL3A"]);
+        assert!(work.subjects.is_none());
+
+        let work: Work = serde_json::from_str(r#"{"title": "A", "authors": {"key": "/authors/OL1A"}}"#).unwrap();
+        assert!(work.authors.is_none());
+    }
+}
@@ -0,0 +1,213 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::extract::DocumentType;
+use crate::{date, format};
+
+/// A document exported from the dump, resolved with the authors and works it references.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+pub enum OutObject<'a> {
+    Book {
+        id: Cow<'a, str>,
+        name: Cow<'a, str>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        subtitle: Option<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        work_id: Option<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        authors: Vec<OutAuthor<'a>>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        publish_year: Option<u32>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        publish_date: Option<String>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        publish_date_precision: Option<date::Precision>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        number_of_pages: Option<u64>,
+
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        publishers: Vec<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        format: Option<format::Format>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        physical_format: Option<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        subjects: Vec<Cow<'a, str>>,
+
+        /// The names of the languages of the book, e.g. `English`, or their MARC code,
+        /// e.g. `eng`, when the language isn't in the dump or wasn't read yet by a single pass extraction.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        languages: Vec<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        isbns: Vec<String>,
+
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        identifiers: BTreeMap<Cow<'a, str>, Vec<Cow<'a, str>>>,
+    },
+    Author {
+        id: Cow<'a, str>,
+        name: Cow<'a, str>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        personal_name: Option<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        alternate_names: Vec<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        birth_date: Option<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        birth_year: Option<u32>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        death_date: Option<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        death_year: Option<u32>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bio: Option<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        photos: Vec<i64>,
+
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        remote_ids: BTreeMap<Cow<'a, str>, Cow<'a, str>>,
+    },
+    Work {
+        id: Cow<'a, str>,
+        name: Cow<'a, str>,
+
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        authors: Vec<OutAuthor<'a>>,
+
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        subjects: Vec<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<Cow<'a, str>>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        first_publish_date: Option<Cow<'a, str>>,
+    },
+}
+
+impl OutObject<'_> {
+    pub fn document_type(&self) -> DocumentType {
+        match self {
+            OutObject::Book { .. } => DocumentType::Book,
+            OutObject::Author { .. } => DocumentType::Author,
+            OutObject::Work { .. } => DocumentType::Work,
+        }
+    }
+
+    /// Copies the borrowed strings to get a document that outlives the dump and the store.
+    pub fn into_owned(self) -> OutObject<'static> {
+        match self {
+            OutObject::Book {
+                id, name, subtitle, work_id, authors, publish_year, publish_date, publish_date_precision,
+                number_of_pages, publishers, format, physical_format, subjects, languages, isbns, identifiers,
+            } => OutObject::Book {
+                id: owned(id),
+                name: owned(name),
+                subtitle: subtitle.map(owned),
+                work_id: work_id.map(owned),
+                authors: authors.into_iter().map(OutAuthor::into_owned).collect(),
+                publish_year,
+                publish_date,
+                publish_date_precision,
+                number_of_pages,
+                publishers: publishers.into_iter().map(owned).collect(),
+                format,
+                physical_format: physical_format.map(owned),
+                subjects: subjects.into_iter().map(owned).collect(),
+                languages: languages.into_iter().map(owned).collect(),
+                isbns,
+                identifiers: identifiers.into_iter()
+                    .map(|(scheme, values)| (owned(scheme), values.into_iter().map(owned).collect()))
+                    .collect(),
+            },
+            OutObject::Author {
+                id, name, personal_name, alternate_names, birth_date, birth_year,
+                death_date, death_year, bio, photos, remote_ids,
+            } => OutObject::Author {
+                id: owned(id),
+                name: owned(name),
+                personal_name: personal_name.map(owned),
+                alternate_names: alternate_names.into_iter().map(owned).collect(),
+                birth_date: birth_date.map(owned),
+                birth_year,
+                death_date: death_date.map(owned),
+                death_year,
+                bio: bio.map(owned),
+                photos,
+                remote_ids: remote_ids.into_iter().map(|(k, v)| (owned(k), owned(v))).collect(),
+            },
+            OutObject::Work { id, name, authors, subjects, description, first_publish_date } => OutObject::Work {
+                id: owned(id),
+                name: owned(name),
+                authors: authors.into_iter().map(OutAuthor::into_owned).collect(),
+                subjects: subjects.into_iter().map(owned).collect(),
+                description: description.map(owned),
+                first_publish_date: first_publish_date.map(owned),
+            },
+        }
+    }
+}
+
+/// An author of a book or a work, either its bare name or an object with its id.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum OutAuthor<'a> {
+    Name(Cow<'a, str>),
+    Object {
+        id: Cow<'a, str>,
+        name: Cow<'a, str>,
+    },
+}
+
+impl<'a> OutAuthor<'a> {
+    pub fn new(id: Cow<'a, str>, name: Cow<'a, str>, nested: bool) -> OutAuthor<'a> {
+        if nested {
+            OutAuthor::Object { id, name }
+        } else {
+            OutAuthor::Name(name)
+        }
+    }
+
+    pub fn into_owned(self) -> OutAuthor<'static> {
+        match self {
+            OutAuthor::Name(name) => OutAuthor::Name(owned(name)),
+            OutAuthor::Object { id, name } => OutAuthor::Object { id: owned(id), name: owned(name) },
+        }
+    }
+}
+
+/// The serialized name of a unit variant, e.g. `hardcover`.
+#[cfg(any(feature = "columnar", feature = "sqlite"))]
+pub(crate) fn variant_name<T: Serialize>(value: T) -> anyhow::Result<String> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(name) => Ok(name),
+        value => anyhow::bail!("{} isn't the name of a variant", value),
+    }
+}
+
+fn owned(text: Cow<str>) -> Cow<'static, str> {
+    Cow::Owned(text.into_owned())
+}
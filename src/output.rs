@@ -0,0 +1,547 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+#[cfg(feature = "columnar")]
+use open_library_extractor::columnar::{ColumnarFormat, ColumnarSink};
+#[cfg(feature = "meilisearch")]
+use open_library_extractor::meilisearch::MeilisearchSink;
+#[cfg(feature = "sqlite")]
+use open_library_extractor::sqlite::SqliteSink;
+use open_library_extractor::tabular::{CsvOptions, CsvSink};
+use open_library_extractor::{ByTypeSink, DocumentType, NdJsonEncoder, NdJsonSink, Options, Sink};
+
+use crate::cli::OutputArgs;
+
+/// The format of the documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Format {
+    /// A JSON document per line.
+    #[default]
+    Ndjson,
+    /// Typed columns in Parquet files, one per type of documents.
+    Parquet,
+    /// Typed columns in Arrow IPC files, one per type of documents.
+    Arrow,
+    /// Normalized tables in a SQLite database, with a full-text index of the books.
+    Sqlite,
+    /// Flat rows of the books and the authors, separated by commas.
+    Csv,
+    /// Flat rows of the books and the authors, separated by tabs.
+    Tsv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    /// Zstandard, compressed on the worker threads.
+    Zstd,
+}
+
+impl Compression {
+    /// Guesses the compression from the extension of the path, e.g. `books.ndjson.gz`.
+    pub fn from_path(path: &Path) -> Compression {
+        match path.extension() {
+            Some(extension) if extension == "gz" => Compression::Gzip,
+            Some(extension) if extension == "zst" || extension == "zstd" => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// The extension of the files compressed this way, e.g. `.gz`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+/// How the documents are split into many files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sharding {
+    /// Into this number of files, the batches of documents being dealt in turn.
+    Count(usize),
+    /// Into files that contain at most this number of bytes of uncompressed documents,
+    /// unless a single document is bigger.
+    MaxSize(usize),
+}
+
+/// A file or the standard output, optionally compressed.
+enum Stream {
+    Plain(io::BufWriter<Box<dyn Write + Send>>),
+    Gzip(GzEncoder<io::BufWriter<Box<dyn Write + Send>>>),
+    Zstd(zstd::Encoder<'static, io::BufWriter<Box<dyn Write + Send>>>),
+}
+
+impl Stream {
+    fn create(path: Option<&Path>, compression: Compression, threads: usize) -> anyhow::Result<Stream> {
+        let writer: Box<dyn Write + Send> = match path {
+            Some(path) => {
+                let file = File::create(path).with_context(|| format!("while creating {:?}", path))?;
+                Box::new(file)
+            },
+            None => Box::new(io::stdout()),
+        };
+
+        let writer = io::BufWriter::new(writer);
+        match compression {
+            Compression::None => Ok(Stream::Plain(writer)),
+            Compression::Gzip => Ok(Stream::Gzip(GzEncoder::new(writer, flate2::Compression::default()))),
+            Compression::Zstd => {
+                let mut encoder = zstd::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+                encoder.multithread(threads as u32)?;
+                Ok(Stream::Zstd(encoder))
+            },
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let writer = match self {
+            Stream::Plain(writer) => writer,
+            Stream::Gzip(encoder) => encoder.finish()?,
+            Stream::Zstd(encoder) => encoder.finish()?,
+        };
+        writer.into_inner().map_err(|e| e.into_error())?.flush()
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(writer) => writer.write(buf),
+            Stream::Gzip(encoder) => encoder.write(buf),
+            Stream::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(writer) => writer.flush(),
+            Stream::Gzip(encoder) => encoder.flush(),
+            Stream::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// The destination of the documents, a file or the standard output, optionally compressed
+/// and split into many files, e.g. `books-0001.ndjson.zst`, `books-0002.ndjson.zst`...
+///
+/// The files are only split between lines, the documents are never cut.
+pub struct Output {
+    path: Option<PathBuf>,
+    compression: Compression,
+    /// The number of threads that compress the documents with zstd.
+    threads: usize,
+    sharding: Option<Sharding>,
+    /// The open files, a single one unless the documents are split into a number of files.
+    streams: Vec<Stream>,
+    /// The number of the file being written, from zero.
+    shard: usize,
+    /// The number of bytes written to the file being written.
+    written: usize,
+}
+
+impl Output {
+    /// Creates the output file, or writes to the standard output if there is no path,
+    /// the zstd compression runs on the given number of threads.
+    /// The files of the shards are only created when documents are written to them.
+    pub fn create(
+        path: Option<&Path>,
+        compression: Option<Compression>,
+        sharding: Option<Sharding>,
+        threads: usize,
+    ) -> anyhow::Result<Output>
+    {
+        let compression = compression.or_else(|| path.map(Compression::from_path)).unwrap_or(Compression::None);
+        let streams = match (path, sharding) {
+            (None, Some(_)) => anyhow::bail!("the documents can only be split into files, not the standard output"),
+            (_, Some(_)) => Vec::new(),
+            (path, None) => vec![Stream::create(path, compression, threads)?],
+        };
+        let path = path.map(ToOwned::to_owned);
+        Ok(Output { path, compression, threads, sharding, streams, shard: 0, written: 0 })
+    }
+
+    /// Finishes the compressed streams and flushes the remaining documents.
+    pub fn finish(self) -> io::Result<()> {
+        self.streams.into_iter().try_for_each(Stream::finish)
+    }
+
+    /// The path of a shard, the number is inserted before the extensions, e.g. `books-0001.ndjson.zst`.
+    fn shard_path(&self, shard: usize) -> PathBuf {
+        let path = self.path.as_deref().unwrap_or_else(|| Path::new(""));
+        let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let (stem, extensions) = name.split_at(name.find('.').unwrap_or(name.len()));
+        path.with_file_name(format!("{}-{:04}{}", stem, shard + 1, extensions))
+    }
+
+    /// The stream of the shard being written, created if needed.
+    fn stream(&mut self) -> anyhow::Result<&mut Stream> {
+        let index = match self.sharding {
+            Some(Sharding::Count(_)) => self.shard,
+            Some(Sharding::MaxSize(_)) | None => 0,
+        };
+        if index == self.streams.len() {
+            let stream = Stream::create(Some(&self.shard_path(self.shard)), self.compression, self.threads)?;
+            self.streams.push(stream);
+        }
+        Ok(&mut self.streams[index])
+    }
+
+    /// Finishes the file being written and starts the next one.
+    fn next_shard(&mut self) -> io::Result<()> {
+        match self.sharding {
+            Some(Sharding::Count(count)) => self.shard = (self.shard + 1) % count,
+            Some(Sharding::MaxSize(_)) => {
+                if let Some(stream) = self.streams.pop() {
+                    stream.finish()?;
+                }
+                self.shard += 1;
+            },
+            None => (),
+        }
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let end = match self.sharding {
+            None => return self.streams[0].write(buf),
+            // The shards are switched after the last line of the buffer.
+            Some(Sharding::Count(_)) => buf.iter().rposition(|b| *b == b'\n').map_or(buf.len(), |i| i + 1),
+            Some(Sharding::MaxSize(max)) if self.written + buf.len() <= max => buf.len(),
+            // Only the lines that fit are written, a line bigger than the maximum gets its own shard.
+            Some(Sharding::MaxSize(max)) => match buf[..max.saturating_sub(self.written)].iter().rposition(|b| *b == b'\n') {
+                Some(i) => i + 1,
+                None if self.written == 0 => buf.iter().position(|b| *b == b'\n').map_or(buf.len(), |i| i + 1),
+                None => {
+                    self.next_shard()?;
+                    return self.write(buf);
+                },
+            },
+        };
+
+        let stream = self.stream().map_err(io::Error::other)?;
+        stream.write_all(&buf[..end])?;
+        self.written += end;
+        if matches!(self.sharding, Some(Sharding::Count(_))) && buf[..end].ends_with(b"\n") {
+            self.next_shard()?;
+        }
+        Ok(end)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.streams.iter_mut().try_for_each(Stream::flush)
+    }
+}
+
+/// The sinks of the documents, a single output or a file per type of documents.
+pub enum Outputs {
+    Single(NdJsonSink<Output>),
+    ByType(ByTypeSink<NdJsonSink<Output>>),
+    /// The Parquet or Arrow files, each holding a single type of documents.
+    #[cfg(feature = "columnar")]
+    Columnar(ByTypeSink<ColumnarSink<Output>>),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteSink),
+    /// The CSV or TSV files of the books and the authors.
+    Csv(ByTypeSink<CsvSink<Output>>),
+    #[cfg(feature = "meilisearch")]
+    Meilisearch(MeilisearchSink),
+}
+
+/// The error of an output whose feature isn't enabled in this build.
+pub fn disabled(output: &str, feature: &str) -> anyhow::Error {
+    anyhow::anyhow!("{} requires the binary to be built with the {:?} feature", output, feature)
+}
+
+impl Outputs {
+    /// Creates the outputs of the types of documents of the options, adjusting the options to the format.
+    /// The compressed outputs use the given number of threads.
+    pub fn create(args: &OutputArgs, options: &mut Options, encoder: NdJsonEncoder, threads: usize) -> anyhow::Result<Outputs> {
+        let types: Vec<_> = DocumentType::ALL.iter().copied().filter(|kind| options.includes(*kind)).collect();
+        if args.no_type_tag && args.output_dir.is_none() && types.len() != 1 {
+            anyhow::bail!("the type field can only be removed with --output-dir or a single type with --types");
+        }
+        if cfg!(not(feature = "meilisearch")) && args.meilisearch_settings.is_some() {
+            return Err(disabled("writing the settings of a Meilisearch index", "meilisearch"));
+        }
+        if let Some(url) = &args.meilisearch_url {
+            return Outputs::create_meilisearch(args, url, encoder);
+        }
+
+        match args.format {
+            Format::Ndjson => Outputs::create_ndjson(args, &types, encoder, threads),
+            Format::Sqlite => Outputs::create_sqlite(args, options),
+            Format::Csv | Format::Tsv => Outputs::create_csv(args, options, threads),
+            Format::Parquet | Format::Arrow => Outputs::create_columnar(args, &types),
+        }
+    }
+
+    #[cfg(feature = "columnar")]
+    fn create_columnar(args: &OutputArgs, types: &[DocumentType]) -> anyhow::Result<Outputs> {
+        let format = if args.format == Format::Arrow { ColumnarFormat::ArrowIpc } else { ColumnarFormat::Parquet };
+        if args.sharding().is_some() || matches!(args.compression, Some(Compression::Gzip | Compression::Zstd)) {
+            anyhow::bail!("the {} files are compressed by themselves and can't be split into shards", format.extension());
+        }
+
+        let mut sinks = Vec::new();
+        match (&args.output_dir, types) {
+            (Some(dir), _) => {
+                fs::create_dir_all(dir).with_context(|| format!("while creating {:?}", dir))?;
+                for kind in types.iter().copied() {
+                    let path = dir.join(format!("{}s.{}", kind.name(), format.extension()));
+                    let output = Output::create(Some(&path), Some(Compression::None), None, 1)?;
+                    sinks.push((kind, ColumnarSink::new(output, kind, format)?));
+                }
+            },
+            (None, [kind]) => {
+                let output = Output::create(args.output.as_deref(), Some(Compression::None), None, 1)?;
+                sinks.push((*kind, ColumnarSink::new(output, *kind, format)?));
+            },
+            (None, _) => anyhow::bail!(
+                "each type of documents is written to its own {} file, use --output-dir or a single type with --types",
+                format.extension(),
+            ),
+        }
+        Ok(Outputs::Columnar(ByTypeSink::new(sinks)))
+    }
+
+    #[cfg(not(feature = "columnar"))]
+    fn create_columnar(_: &OutputArgs, _: &[DocumentType]) -> anyhow::Result<Outputs> {
+        Err(disabled("the Parquet or Arrow format", "columnar"))
+    }
+
+    #[cfg(feature = "meilisearch")]
+    fn create_meilisearch(args: &OutputArgs, url: &str, encoder: NdJsonEncoder) -> anyhow::Result<Outputs> {
+        if args.format != Format::Ndjson || args.sharding().is_some() || args.compression.is_some() {
+            anyhow::bail!("the documents are sent to Meilisearch as ndJSON, without compression nor shards");
+        }
+
+        let sink = MeilisearchSink::new(url, &args.meilisearch_index, encoder.without_type(args.no_type_tag))
+            .api_key(args.meilisearch_api_key.clone())
+            .max_payload_size(args.max_payload_size);
+        Ok(Outputs::Meilisearch(sink))
+    }
+
+    #[cfg(not(feature = "meilisearch"))]
+    fn create_meilisearch(_: &OutputArgs, _: &str, _: NdJsonEncoder) -> anyhow::Result<Outputs> {
+        Err(disabled("sending the documents to Meilisearch", "meilisearch"))
+    }
+
+    fn create_csv(args: &OutputArgs, options: &mut Options, threads: usize) -> anyhow::Result<Outputs> {
+        if args.sharding().is_some() {
+            anyhow::bail!("the CSV files can't be split into shards");
+        }
+        // Only the books and the authors can be flattened.
+        if options.types.contains(&DocumentType::Work) {
+            anyhow::bail!("the works can't be written as CSV");
+        }
+        if options.types.is_empty() {
+            options.types = vec![DocumentType::Book, DocumentType::Author];
+        }
+
+        let (delimiter, extension) = if args.format == Format::Tsv { (b'\t', "tsv") } else { (b',', "csv") };
+        let csv_options = |kind| {
+            let columns = match kind {
+                DocumentType::Book => &args.book_columns,
+                _ => &args.author_columns,
+            };
+            let columns = Some(columns.clone()).filter(|columns| !columns.is_empty());
+            CsvOptions { delimiter, columns, separator: args.array_separator.clone(), explode: args.explode.clone() }
+        };
+
+        let mut sinks = Vec::new();
+        match (&args.output_dir, options.types.as_slice()) {
+            (Some(dir), types) => {
+                fs::create_dir_all(dir).with_context(|| format!("while creating {:?}", dir))?;
+                let compression = args.compression.map_or("", Compression::extension);
+                for kind in types.iter().copied() {
+                    let path = dir.join(format!("{}s.{}{}", kind.name(), extension, compression));
+                    let output = Output::create(Some(&path), args.compression, None, threads)?;
+                    sinks.push((kind, CsvSink::new(output, kind, csv_options(kind))?));
+                }
+            },
+            (None, [kind]) => {
+                let output = Output::create(args.output.as_deref(), args.compression, None, threads)?;
+                sinks.push((*kind, CsvSink::new(output, *kind, csv_options(*kind))?));
+            },
+            (None, _) => anyhow::bail!(
+                "the books and the authors are written to their own {} files, use --output-dir or a single type with --types",
+                extension,
+            ),
+        }
+
+        if let Some(explode) = &args.explode {
+            if !sinks.iter().any(|(_, sink)| sink.columns().contains(explode)) {
+                anyhow::bail!("{:?} isn't one of the columns of the CSV files and can't be exploded", explode);
+            }
+        }
+        Ok(Outputs::Csv(ByTypeSink::new(sinks)))
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn create_sqlite(args: &OutputArgs, options: &mut Options) -> anyhow::Result<Outputs> {
+        if args.sharding().is_some() || args.compression.is_some() {
+            anyhow::bail!("the SQLite database can't be compressed nor split into shards");
+        }
+        let path = match (&args.output, &args.output_dir) {
+            (Some(path), None) => path,
+            _ => anyhow::bail!("the SQLite database is written to a single file, given with --output"),
+        };
+
+        // The authors of the books and works are joined with the authors table by their ids.
+        options.nested_authors = true;
+        Ok(Outputs::Sqlite(SqliteSink::create(path)?))
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    fn create_sqlite(_: &OutputArgs, _: &mut Options) -> anyhow::Result<Outputs> {
+        Err(disabled("the SQLite format", "sqlite"))
+    }
+
+    fn create_ndjson(
+        args: &OutputArgs,
+        types: &[DocumentType],
+        encoder: NdJsonEncoder,
+        threads: usize,
+    ) -> anyhow::Result<Outputs>
+    {
+        let encoder = encoder.without_type(args.no_type_tag);
+        let dir = match &args.output_dir {
+            Some(dir) => dir,
+            None => {
+                let output = Output::create(args.output.as_deref(), args.compression, args.sharding(), threads)?;
+                return Ok(Outputs::Single(NdJsonSink::new(output, encoder)));
+            },
+        };
+
+        fs::create_dir_all(dir).with_context(|| format!("while creating {:?}", dir))?;
+        let extension = args.compression.map_or("", Compression::extension);
+
+        let mut sinks = Vec::new();
+        for kind in types.iter().copied() {
+            let path = dir.join(format!("{}s.ndjson{}", kind.name(), extension));
+            let output = Output::create(Some(&path), args.compression, args.sharding(), threads)?;
+            sinks.push((kind, NdJsonSink::new(output, encoder.clone())));
+        }
+        Ok(Outputs::ByType(ByTypeSink::new(sinks)))
+    }
+
+    /// Finishes the sinks and the compressed streams of the outputs.
+    pub fn finish(self) -> anyhow::Result<()> {
+        match self {
+            Outputs::Single(mut sink) => {
+                sink.finish()?;
+                sink.into_inner().finish()?;
+            },
+            Outputs::ByType(mut sink) => {
+                sink.finish()?;
+                for (_, sink) in sink.into_inner() {
+                    sink.into_inner().finish()?;
+                }
+            },
+            #[cfg(feature = "columnar")]
+            Outputs::Columnar(mut sink) => {
+                sink.finish()?;
+                for (_, sink) in sink.into_inner() {
+                    sink.into_inner()?.finish()?;
+                }
+            },
+            #[cfg(feature = "sqlite")]
+            Outputs::Sqlite(mut sink) => sink.finish()?,
+            Outputs::Csv(mut sink) => {
+                sink.finish()?;
+                for (_, sink) in sink.into_inner() {
+                    sink.into_inner().finish()?;
+                }
+            },
+            #[cfg(feature = "meilisearch")]
+            Outputs::Meilisearch(mut sink) => sink.finish()?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes the batches of lines through a sink, returns the names and contents of the files.
+    fn write_shards(sharding: Sharding, batches: &[Vec<String>]) -> Vec<(String, String)> {
+        let dir = tempfile::tempdir().unwrap();
+        let output = Output::create(Some(&dir.path().join("books.ndjson")), None, Some(sharding), 1).unwrap();
+        let mut sink = NdJsonSink::new(output, NdJsonEncoder::default());
+        for batch in batches {
+            sink.write(batch.concat().into_bytes()).unwrap();
+        }
+        sink.finish().unwrap();
+        sink.into_inner().finish().unwrap();
+
+        let mut files: Vec<_> = fs::read_dir(dir.path()).unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (entry.file_name().into_string().unwrap(), fs::read_to_string(entry.path()).unwrap())
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// A line of JSON of the given size, newline included.
+    fn line(id: usize, size: usize) -> String {
+        let prefix = format!("{{\"id\":\"{}\",\"name\":\"", id);
+        format!("{}{}\"}}\n", prefix, "a".repeat(size - prefix.len() - 3))
+    }
+
+    #[test]
+    fn split_into_shards_of_a_maximum_size() {
+        let batches = vec![
+            vec![line(0, 40), line(1, 40), line(2, 40)],
+            vec![line(3, 30)],
+            // Bigger than a shard by itself.
+            vec![line(4, 250), line(5, 25)],
+            vec![line(6, 100)],
+        ];
+        let files = write_shards(Sharding::MaxSize(100), &batches);
+
+        let names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+        let expected: Vec<_> = (1..=5).map(|shard| format!("books-{:04}.ndjson", shard)).collect();
+        assert_eq!(names, expected);
+        let contents: Vec<_> = files.iter().map(|(_, content)| content.as_str()).collect();
+        assert_eq!(contents.concat(), batches.concat().concat());
+        for content in contents {
+            assert!(content.ends_with('\n'));
+            assert!(content.len() <= 100 || content.lines().count() == 1);
+            for line in content.lines() {
+                serde_json::from_str::<serde_json::Value>(line).unwrap();
+            }
+        }
+        assert_eq!(files[0].1, [line(0, 40), line(1, 40)].concat());
+        assert_eq!(files[2].1, line(4, 250));
+    }
+
+    #[test]
+    fn deal_the_batches_to_a_number_of_shards() {
+        let batch = |i: usize| vec![line(2 * i, 30), line(2 * i + 1, 30)];
+        let files = write_shards(Sharding::Count(3), &[batch(0), batch(1), batch(2), batch(3)]);
+        assert_eq!(files, [
+            ("books-0001.ndjson".to_owned(), [batch(0), batch(3)].concat().concat()),
+            ("books-0002.ndjson".to_owned(), batch(1).concat()),
+            ("books-0003.ndjson".to_owned(), batch(2).concat()),
+        ]);
+
+        // The shards are only created when documents are written to them.
+        let files = write_shards(Sharding::Count(5), &[batch(0), batch(1)]);
+        assert_eq!(files.len(), 2);
+        assert!(write_shards(Sharding::MaxSize(100), &[]).is_empty());
+    }
+}
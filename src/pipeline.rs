@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+use std::thread;
+
+use anyhow::anyhow;
+use crossbeam_channel::bounded;
+
+/// Runs `produce` on its own thread, processes the batches it sends on `threads`
+/// worker threads and gives the results to `consume`, on the calling thread and in
+/// the order the batches were produced.
+pub fn run<B, R, P, F, C>(threads: usize, produce: P, process: F, mut consume: C) -> anyhow::Result<()>
+where
+    B: Send,
+    R: Send,
+    P: FnOnce(&mut dyn FnMut(B) -> anyhow::Result<()>) -> anyhow::Result<()> + Send,
+    F: Fn(B) -> anyhow::Result<R> + Sync,
+    C: FnMut(R) -> anyhow::Result<()>,
+{
+    let threads = threads.max(1);
+    let (batch_sender, batch_receiver) = bounded::<(usize, B)>(threads * 2);
+    let (result_sender, result_receiver) = bounded::<(usize, anyhow::Result<R>)>(threads * 2);
+
+    thread::scope(|s| {
+        let producer = s.spawn(move || {
+            let mut count = 0;
+            produce(&mut |batch| {
+                batch_sender.send((count, batch)).map_err(|_| anyhow!("the workers stopped"))?;
+                count += 1;
+                Ok(())
+            })
+        });
+
+        for _ in 0..threads {
+            let batch_receiver = batch_receiver.clone();
+            let result_sender = result_sender.clone();
+            let process = &process;
+            s.spawn(move || {
+                for (i, batch) in batch_receiver {
+                    if result_sender.send((i, process(batch))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        drop(batch_receiver);
+        drop(result_sender);
+
+        // The results are received in any order, we keep them until it is their turn.
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (i, result) in result_receiver {
+            pending.insert(i, result);
+            while let Some(result) = pending.remove(&next) {
+                consume(result?)?;
+                next += 1;
+            }
+        }
+
+        producer.join().map_err(|_| anyhow!("the producer thread panicked"))?
+    })
+}
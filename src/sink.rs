@@ -0,0 +1,270 @@
+use std::io;
+
+use crate::extract::DocumentType;
+use crate::object::OutObject;
+
+/// Prepares the documents for a sink, on the worker threads.
+pub trait Encoder: Sync {
+    /// The documents of a batch, once prepared.
+    type Batch: Default + Send;
+
+    fn encode(&self, batch: &mut Self::Batch, object: OutObject) -> anyhow::Result<()>;
+}
+
+/// A destination of the extracted documents.
+///
+/// The documents are prepared in batches by the encoder of the sink, on the worker
+/// threads, then the batches are written in order, on the calling thread.
+pub trait Sink {
+    type Encoder: Encoder;
+
+    fn encoder(&self) -> Self::Encoder;
+
+    fn write(&mut self, batch: <Self::Encoder as Encoder>::Batch) -> anyhow::Result<()>;
+
+    /// Called once every document has been written.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Copies the documents to give them as they are to the sink.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OwnedEncoder;
+
+impl Encoder for OwnedEncoder {
+    type Batch = Vec<OutObject<'static>>;
+
+    fn encode(&self, batch: &mut Self::Batch, object: OutObject) -> anyhow::Result<()> {
+        batch.push(object.into_owned());
+        Ok(())
+    }
+}
+
+/// Serializes the documents as lines of JSON, optionally with only some of their fields.
+#[derive(Debug, Clone, Default)]
+pub struct NdJsonEncoder {
+    /// The fields of the documents to keep, all of them if `None`.
+    /// The `id` field is always kept, and so is the `type` one unless it is removed.
+    pub(crate) fields: Option<Vec<String>>,
+    /// Whether the `type` field is removed, e.g. when a file only holds one type of documents.
+    pub(crate) without_type: bool,
+}
+
+impl NdJsonEncoder {
+    pub fn new(fields: Option<Vec<String>>) -> NdJsonEncoder {
+        NdJsonEncoder { fields, without_type: false }
+    }
+
+    /// Removes the `type` field of the documents.
+    pub fn without_type(mut self, without_type: bool) -> NdJsonEncoder {
+        self.without_type = without_type;
+        self
+    }
+}
+
+impl Encoder for NdJsonEncoder {
+    type Batch = Vec<u8>;
+
+    fn encode(&self, buffer: &mut Vec<u8>, object: OutObject) -> anyhow::Result<()> {
+        // The documents are only filtered when some of their fields are removed.
+        if self.fields.is_none() && !self.without_type {
+            serde_json::to_writer(&mut *buffer, &object)?;
+        } else {
+            let keep = |field: &str| match field {
+                "type" => !self.without_type,
+                "id" => true,
+                field => self.fields.as_ref().is_none_or(|fields| fields.iter().any(|f| f == field)),
+            };
+            let mut value = serde_json::to_value(&object)?;
+            if let Some(object) = value.as_object_mut() {
+                *object = std::mem::take(object).into_iter().filter(|(field, _)| keep(field)).collect();
+            }
+            serde_json::to_writer(&mut *buffer, &value)?;
+        }
+        buffer.push(b'\n');
+        Ok(())
+    }
+}
+
+/// Writes the documents as ndJSON.
+pub struct NdJsonSink<W> {
+    writer: W,
+    encoder: NdJsonEncoder,
+}
+
+impl<W: io::Write> NdJsonSink<W> {
+    pub fn new(writer: W, encoder: NdJsonEncoder) -> NdJsonSink<W> {
+        NdJsonSink { writer, encoder }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: io::Write> Sink for NdJsonSink<W> {
+    type Encoder = NdJsonEncoder;
+
+    fn encoder(&self) -> NdJsonEncoder {
+        self.encoder.clone()
+    }
+
+    fn write(&mut self, batch: Vec<u8>) -> anyhow::Result<()> {
+        self.writer.write_all(&batch).map_err(Into::into)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.writer.flush().map_err(Into::into)
+    }
+}
+
+/// Writes each type of documents into its own sink, e.g. one file per type.
+/// The documents of the types without a sink are ignored.
+pub struct ByTypeSink<S> {
+    sinks: Vec<(DocumentType, S)>,
+}
+
+impl<S: Sink> ByTypeSink<S> {
+    pub fn new(sinks: Vec<(DocumentType, S)>) -> ByTypeSink<S> {
+        ByTypeSink { sinks }
+    }
+
+    pub fn into_inner(self) -> Vec<(DocumentType, S)> {
+        self.sinks
+    }
+}
+
+/// Prepares the documents with the encoder of the sink of their type.
+pub struct ByTypeEncoder<E> {
+    encoders: Vec<(DocumentType, E)>,
+}
+
+impl<E: Encoder> Encoder for ByTypeEncoder<E> {
+    type Batch = Vec<(DocumentType, E::Batch)>;
+
+    fn encode(&self, batch: &mut Self::Batch, object: OutObject) -> anyhow::Result<()> {
+        let kind = object.document_type();
+        let encoder = match self.encoders.iter().find(|(k, _)| *k == kind) {
+            Some((_, encoder)) => encoder,
+            None => return Ok(()),
+        };
+
+        let index = match batch.iter().position(|(k, _)| *k == kind) {
+            Some(index) => index,
+            None => {
+                batch.push((kind, E::Batch::default()));
+                batch.len() - 1
+            },
+        };
+        encoder.encode(&mut batch[index].1, object)
+    }
+}
+
+impl<S: Sink> Sink for ByTypeSink<S> {
+    type Encoder = ByTypeEncoder<S::Encoder>;
+
+    fn encoder(&self) -> Self::Encoder {
+        ByTypeEncoder { encoders: self.sinks.iter().map(|(kind, sink)| (*kind, sink.encoder())).collect() }
+    }
+
+    fn write(&mut self, batch: Vec<(DocumentType, <S::Encoder as Encoder>::Batch)>) -> anyhow::Result<()> {
+        for (kind, batch) in batch {
+            if let Some((_, sink)) = self.sinks.iter_mut().find(|(k, _)| *k == kind) {
+                sink.write(batch)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.sinks.iter_mut().try_for_each(|(_, sink)| sink.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::Options;
+    use crate::testing::extract_sample;
+
+    #[test]
+    fn write_ndjson_with_some_fields() {
+        let encoder = NdJsonEncoder::new(Some(vec!["name".to_owned()]));
+        let mut sink = NdJsonSink::new(Vec::new(), encoder);
+        extract_sample(Options::default(), &mut sink).unwrap();
+
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(output.lines().count(), 197);
+        for line in output.lines() {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            let mut fields: Vec<_> = value.as_object().unwrap().keys().map(String::as_str).collect();
+            fields.sort_unstable();
+            assert_eq!(fields, ["id", "name", "type"]);
+        }
+    }
+
+    #[test]
+    fn write_each_type_into_its_own_sink() {
+        let encoder = NdJsonEncoder::new(None).without_type(true);
+        let sinks = vec![
+            (DocumentType::Book, NdJsonSink::new(Vec::new(), encoder.clone())),
+            (DocumentType::Author, NdJsonSink::new(Vec::new(), encoder)),
+        ];
+        let mut sink = ByTypeSink::new(sinks);
+        extract_sample(Options::default(), &mut sink).unwrap();
+
+        let outputs: Vec<_> = sink.into_inner().into_iter()
+            .map(|(kind, sink)| (kind, String::from_utf8(sink.into_inner()).unwrap()))
+            .collect();
+        assert_eq!(outputs[0].0, DocumentType::Book);
+        assert_eq!(outputs[0].1.lines().count(), 100);
+        assert_eq!(outputs[1].0, DocumentType::Author);
+        assert_eq!(outputs[1].1.lines().count(), 97);
+        for (_, output) in &outputs {
+            for line in output.lines() {
+                let value: serde_json::Value = serde_json::from_str(line).unwrap();
+                assert!(value.get("type").is_none());
+                assert!(value.get("id").is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn plug_a_custom_sink() {
+        #[derive(Default)]
+        struct NamesSink {
+            names: Vec<String>,
+            finished: bool,
+        }
+
+        impl Sink for NamesSink {
+            type Encoder = OwnedEncoder;
+
+            fn encoder(&self) -> OwnedEncoder {
+                OwnedEncoder
+            }
+
+            fn write(&mut self, batch: Vec<OutObject<'static>>) -> anyhow::Result<()> {
+                for object in batch {
+                    if let OutObject::Author { name, .. } = object {
+                        self.names.push(name.into_owned());
+                    }
+                }
+                Ok(())
+            }
+
+            fn finish(&mut self) -> anyhow::Result<()> {
+                self.finished = true;
+                Ok(())
+            }
+        }
+
+        let mut sink = NamesSink::default();
+        extract_sample(Options::default(), &mut sink).unwrap();
+
+        assert!(sink.finished);
+        assert_eq!(sink.names.len(), 97);
+        assert!(sink.names.iter().any(|name| name == "Harald A. Enge"));
+    }
+}
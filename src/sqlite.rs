@@ -0,0 +1,302 @@
+//! Writes the documents into normalized SQLite tables, with a full-text index of the books.
+
+use std::convert::TryFrom;
+use std::path::Path;
+
+use rusqlite::{params, Connection, Transaction};
+
+use crate::object::{variant_name, OutAuthor, OutObject};
+use crate::sink::{OwnedEncoder, Sink};
+
+/// The tables are created without any index nor constraint, to be filled faster.
+const SCHEMA: &str = "
+    CREATE TABLE books (
+        id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        subtitle TEXT,
+        work_id TEXT,
+        publish_year INTEGER,
+        publish_date TEXT,
+        publish_date_precision TEXT,
+        number_of_pages INTEGER,
+        publishers TEXT,
+        format TEXT,
+        physical_format TEXT,
+        languages TEXT
+    );
+    CREATE TABLE authors (
+        id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        personal_name TEXT,
+        alternate_names TEXT,
+        birth_date TEXT,
+        birth_year INTEGER,
+        death_date TEXT,
+        death_year INTEGER,
+        bio TEXT,
+        photos TEXT,
+        remote_ids TEXT
+    );
+    CREATE TABLE works (
+        id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        subjects TEXT,
+        description TEXT,
+        first_publish_date TEXT
+    );
+    CREATE TABLE book_authors (
+        book_id TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        author_id TEXT,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE work_authors (
+        work_id TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        author_id TEXT,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE book_subjects (
+        book_id TEXT NOT NULL,
+        subject TEXT NOT NULL
+    );
+    CREATE TABLE identifiers (
+        book_id TEXT NOT NULL,
+        scheme TEXT NOT NULL,
+        value TEXT NOT NULL
+    );
+    CREATE VIRTUAL TABLE books_fts USING fts5(book_id UNINDEXED, name, subtitle, authors);
+";
+
+/// The indexes are only created once every document is written.
+const INDEXES: &str = "
+    CREATE UNIQUE INDEX books_id ON books (id);
+    CREATE INDEX books_work_id ON books (work_id);
+    CREATE INDEX books_publish_year ON books (publish_year);
+    CREATE UNIQUE INDEX authors_id ON authors (id);
+    CREATE UNIQUE INDEX works_id ON works (id);
+    CREATE INDEX book_authors_book_id ON book_authors (book_id);
+    CREATE INDEX book_authors_author_id ON book_authors (author_id);
+    CREATE INDEX work_authors_work_id ON work_authors (work_id);
+    CREATE INDEX work_authors_author_id ON work_authors (author_id);
+    CREATE INDEX book_subjects_book_id ON book_subjects (book_id);
+    CREATE INDEX book_subjects_subject ON book_subjects (subject);
+    CREATE INDEX identifiers_book_id ON identifiers (book_id);
+    CREATE INDEX identifiers_scheme_value ON identifiers (scheme, value);
+    INSERT INTO books_fts (books_fts) VALUES ('optimize');
+    ANALYZE;
+";
+
+/// Writes the documents into a SQLite database, each batch in its own transaction.
+///
+/// The books, authors and works are written to their own tables, the authors of the books
+/// and the works to the `book_authors` and `work_authors` tables, the subjects of the books
+/// to `book_subjects` and their ISBNs and other identifiers to `identifiers`.
+/// The other lists, e.g. the `publishers`, are stored as JSON arrays.
+/// The titles, subtitles and author names of the books are indexed in the `books_fts` FTS5 table.
+///
+/// The ids of the authors are only known when the authors are nested,
+/// see [`Options::nested_authors`](crate::Options::nested_authors).
+pub struct SqliteSink {
+    connection: Connection,
+}
+
+impl SqliteSink {
+    /// Creates the database file, that must not exist already.
+    pub fn create(path: &Path) -> anyhow::Result<SqliteSink> {
+        if path.exists() {
+            anyhow::bail!("{:?} already exists", path);
+        }
+        SqliteSink::new(Connection::open(path)?)
+    }
+
+    /// Creates the tables in the database of this connection.
+    pub fn new(connection: Connection) -> anyhow::Result<SqliteSink> {
+        // The database is rebuilt from scratch when anything goes wrong.
+        connection.execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF;")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteSink { connection })
+    }
+
+    pub fn into_inner(self) -> Connection {
+        self.connection
+    }
+}
+
+impl Sink for SqliteSink {
+    type Encoder = OwnedEncoder;
+
+    fn encoder(&self) -> OwnedEncoder {
+        OwnedEncoder
+    }
+
+    fn write(&mut self, batch: Vec<OutObject<'static>>) -> anyhow::Result<()> {
+        let transaction = self.connection.transaction()?;
+        for object in batch {
+            insert(&transaction, object)?;
+        }
+        transaction.commit().map_err(Into::into)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.connection.execute_batch(INDEXES).map_err(Into::into)
+    }
+}
+
+fn insert(transaction: &Transaction, object: OutObject) -> anyhow::Result<()> {
+    match object {
+        OutObject::Book {
+            id, name, subtitle, work_id, authors, publish_year, publish_date, publish_date_precision,
+            number_of_pages, publishers, format, physical_format, subjects, languages, isbns, identifiers,
+        } => {
+            transaction.prepare_cached("INSERT INTO books VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")?.execute(params![
+                id,
+                name,
+                subtitle,
+                work_id,
+                publish_year,
+                publish_date,
+                publish_date_precision.map(variant_name).transpose()?,
+                number_of_pages.map(i64::try_from).transpose()?,
+                json_array(&publishers)?,
+                format.map(variant_name).transpose()?,
+                physical_format,
+                json_array(&languages)?,
+            ])?;
+            insert_authors(transaction, "INSERT INTO book_authors VALUES (?, ?, ?, ?)", &id, &authors)?;
+
+            let mut statement = transaction.prepare_cached("INSERT INTO book_subjects VALUES (?, ?)")?;
+            for subject in subjects {
+                statement.execute(params![id, subject])?;
+            }
+
+            let mut statement = transaction.prepare_cached("INSERT INTO identifiers VALUES (?, ?, ?)")?;
+            for isbn in isbns {
+                statement.execute(params![id, "isbn", isbn])?;
+            }
+            for (scheme, values) in identifiers {
+                for value in values {
+                    statement.execute(params![id, scheme, value])?;
+                }
+            }
+
+            let author_names: Vec<_> = authors.iter().map(author_name).collect();
+            transaction
+                .prepare_cached("INSERT INTO books_fts VALUES (?, ?, ?, ?)")?
+                .execute(params![id, name, subtitle, author_names.join(", ")])?;
+        },
+        OutObject::Author {
+            id, name, personal_name, alternate_names, birth_date, birth_year,
+            death_date, death_year, bio, photos, remote_ids,
+        } => {
+            transaction.prepare_cached("INSERT INTO authors VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")?.execute(params![
+                id,
+                name,
+                personal_name,
+                json_array(&alternate_names)?,
+                birth_date,
+                birth_year,
+                death_date,
+                death_year,
+                bio,
+                json_array(&photos)?,
+                Some(serde_json::to_string(&remote_ids)?).filter(|_| !remote_ids.is_empty()),
+            ])?;
+        },
+        OutObject::Work { id, name, authors, subjects, description, first_publish_date } => {
+            transaction
+                .prepare_cached("INSERT INTO works VALUES (?, ?, ?, ?, ?)")?
+                .execute(params![id, name, json_array(&subjects)?, description, first_publish_date])?;
+            insert_authors(transaction, "INSERT INTO work_authors VALUES (?, ?, ?, ?)", &id, &authors)?;
+        },
+    }
+    Ok(())
+}
+
+fn insert_authors(transaction: &Transaction, sql: &str, id: &str, authors: &[OutAuthor]) -> anyhow::Result<()> {
+    let mut statement = transaction.prepare_cached(sql)?;
+    for (position, author) in authors.iter().enumerate() {
+        let author_id = match author {
+            OutAuthor::Name(_) => None,
+            OutAuthor::Object { id, .. } => Some(id),
+        };
+        statement.execute(params![id, position, author_id, author_name(author)])?;
+    }
+    Ok(())
+}
+
+fn author_name<'a>(author: &'a OutAuthor) -> &'a str {
+    match author {
+        OutAuthor::Name(name) | OutAuthor::Object { name, .. } => name,
+    }
+}
+
+/// Stores the list as a JSON array, or `NULL` if it is empty.
+fn json_array<T: serde::Serialize>(values: &[T]) -> serde_json::Result<Option<String>> {
+    if values.is_empty() {
+        Ok(None)
+    } else {
+        serde_json::to_string(values).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::Options;
+    use crate::testing::{extract_dump, SAMPLE};
+
+    #[test]
+    fn write_normalized_tables() {
+        let options = Options { nested_authors: true, ..Options::default() };
+        // The books of the sample have no known authors, this one is written by K. Hardono.
+        let book = "/type/edition\t/books/OL1M\t1\t2021-01-01T00:00:00.000000\t\
+            {\"key\": \"/books/OL1M\", \"title\": \"Sejarah Indonesia\", \"authors\": [{\"key\": \"/authors/OL100029A\"}]}\n";
+        let dump = format!("{}{}", SAMPLE, book);
+        let mut sink = SqliteSink::new(Connection::open_in_memory().unwrap()).unwrap();
+        extract_dump(dump.as_bytes(), options, &mut sink).unwrap();
+        let connection = sink.into_inner();
+
+        let count = |table: &str| -> i64 {
+            connection.query_row(&format!("SELECT count(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(count("books"), 101);
+        assert_eq!(count("authors"), 97);
+        assert_eq!(count("book_authors"), 1);
+        assert_eq!(count("books_fts"), 101);
+
+        let (year, work_id): (i64, String) = connection
+            .query_row("SELECT publish_year, work_id FROM books WHERE id = 'OL10000135M'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((year, work_id.as_str()), (1993, "OL7925046W"));
+
+        let book_id: String = connection
+            .query_row("SELECT book_id FROM identifiers WHERE scheme = 'isbn' AND value = '9780107805401'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(book_id, "OL10000135M");
+
+        // Every author of a book can be joined with the authors table.
+        let orphans: i64 = connection
+            .query_row(
+                "SELECT count(*) FROM book_authors LEFT JOIN authors ON authors.id = book_authors.author_id
+                 WHERE authors.id IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(orphans, 0);
+
+        let search = |query: &str| -> Vec<String> {
+            let mut statement = connection.prepare("SELECT book_id FROM books_fts WHERE books_fts MATCH ?").unwrap();
+            let ids = statement.query_map([query], |row| row.get(0)).unwrap();
+            ids.collect::<Result<_, _>>().unwrap()
+        };
+        assert_eq!(search("parliamentary lords subtitle:november"), ["OL10000135M"]);
+
+        assert_eq!(search("authors:hardono sejarah"), ["OL1M"]);
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use anyhow::{anyhow, bail};
+use heed::{Database, Env, EnvOpenOptions, MdbError, types::Str};
+
+/// The tables in which the records of the dump are kept to be looked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Table {
+    AuthorsIdsNames,
+    AuthorsIdsJsons,
+    WorksIdsJsons,
+    Redirects,
+    LanguagesIdsNames,
+    /// The editions left to export once the whole dump is read, all of them in an index.
+    Editions,
+    /// The informations about the index itself, e.g. the date of the dump.
+    Metadata,
+}
+
+impl Table {
+    pub const ALL: [Table; 7] = [
+        Table::AuthorsIdsNames,
+        Table::AuthorsIdsJsons,
+        Table::WorksIdsJsons,
+        Table::Redirects,
+        Table::LanguagesIdsNames,
+        Table::Editions,
+        Table::Metadata,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Table::AuthorsIdsNames => "authors-ids-names",
+            Table::AuthorsIdsJsons => "authors-ids-jsons",
+            Table::WorksIdsJsons => "works-ids-jsons",
+            Table::Redirects => "redirects",
+            Table::LanguagesIdsNames => "languages-ids-names",
+            Table::Editions => "editions",
+            Table::Metadata => "metadata",
+        }
+    }
+
+    fn index(self) -> usize {
+        Table::ALL.iter().position(|t| *t == self).unwrap()
+    }
+}
+
+/// The entries of a table, ordered by key.
+pub type Entries<'a> = Box<dyn Iterator<Item = anyhow::Result<(&'a str, &'a str)>> + 'a>;
+
+/// A consistent view of the tables.
+pub trait StoreReader {
+    fn get(&self, table: Table, key: &str) -> anyhow::Result<Option<&str>>;
+
+    fn len(&self, table: Table) -> anyhow::Result<usize>;
+
+    fn iter(&self, table: Table) -> anyhow::Result<Entries<'_>>;
+}
+
+/// A store that can be read from many threads while a single thread writes into it.
+pub trait LookupStore: Sync {
+    /// Calls `f` with a reader that sees every entry written before this call.
+    fn read(&self, f: &mut dyn FnMut(&dyn StoreReader) -> anyhow::Result<()>) -> anyhow::Result<()>;
+
+    /// Writes the entries, replacing the previous values of the keys.
+    fn write(&self, entries: &[(Table, String, String)]) -> anyhow::Result<()>;
+}
+
+/// The smallest map of the LMDB store, the maps are rounded up to a multiple of it,
+/// which is a multiple of the size of the memory pages.
+pub const MIN_MAP_SIZE: usize = 1024 * 1024;
+
+/// The number of times the map is doubled in a row before giving up, 256 times the size that was full.
+const MAX_GROWTHS: usize = 8;
+
+struct HeedInner {
+    env: Env,
+    databases: Vec<Database<Str, Str>>,
+    map_size: usize,
+}
+
+/// A store backed by an LMDB environment on disk.
+pub struct HeedStore {
+    path: PathBuf,
+    // The environment is reopened with a bigger map when it is full.
+    inner: RwLock<Option<HeedInner>>,
+    on_grow: Option<Box<dyn Fn(usize) + Send + Sync>>,
+}
+
+impl HeedStore {
+    /// Opens or creates the store in this directory, with a map of at least `map_size` bytes
+    /// and at least as big as the store already is.
+    pub fn open(path: impl AsRef<Path>, map_size: usize) -> anyhow::Result<HeedStore> {
+        let path = path.as_ref().to_path_buf();
+        let used = fs::metadata(path.join("data.mdb")).map_or(0, |metadata| metadata.len() as usize);
+        let map_size = map_size.max(used).max(MIN_MAP_SIZE);
+        let map_size = map_size.checked_next_multiple_of(MIN_MAP_SIZE).ok_or_else(|| anyhow!("map size too big"))?;
+        let inner = HeedStore::open_inner(&path, map_size)?;
+        Ok(HeedStore { path, inner: RwLock::new(Some(inner)), on_grow: None })
+    }
+
+    /// Calls `f` with the new size of the map each time it grows, e.g. to report it.
+    pub fn on_grow(mut self, f: impl Fn(usize) + Send + Sync + 'static) -> HeedStore {
+        self.on_grow = Some(Box::new(f));
+        self
+    }
+
+    /// Opens the environment and creates its databases, doubling the map while it is too small for them.
+    fn open_inner(path: &Path, map_size: usize) -> anyhow::Result<HeedInner> {
+        let mut map_size = map_size;
+        for _ in 0..=MAX_GROWTHS {
+            let env = EnvOpenOptions::new()
+                .map_size(map_size)
+                .max_dbs(Table::ALL.len() as u32)
+                .open(path)?;
+
+            let databases = Table::ALL.iter()
+                .map(|table| env.create_database(Some(table.name())))
+                .collect::<heed::Result<_>>();
+
+            match databases {
+                Ok(databases) => return Ok(HeedInner { env, databases, map_size }),
+                Err(heed::Error::Mdb(MdbError::MapFull)) => {
+                    env.prepare_for_closing().wait();
+                    map_size = doubled(map_size)?;
+                },
+                Err(error) => return Err(error.into()),
+            }
+        }
+        bail!("the LMDB map is still full at {} bytes", map_size)
+    }
+
+    /// Closes the environment and reopens it with a map twice as big.
+    fn grow(&self) -> anyhow::Result<()> {
+        let mut inner = self.inner.write().map_err(|_| anyhow!("poisoned store lock"))?;
+        let HeedInner { env, map_size, .. } = inner.take().ok_or_else(|| anyhow!("closed store"))?;
+        env.prepare_for_closing().wait();
+
+        let map_size = doubled(map_size)?;
+        if let Some(on_grow) = &self.on_grow {
+            on_grow(map_size);
+        }
+        *inner = Some(HeedStore::open_inner(&self.path, map_size)?);
+        Ok(())
+    }
+}
+
+/// Twice the size of a map, unless it overflows.
+fn doubled(map_size: usize) -> anyhow::Result<usize> {
+    map_size.checked_mul(2).ok_or_else(|| anyhow!("the LMDB map can't grow beyond {} bytes", map_size))
+}
+
+struct HeedReader<'e> {
+    rtxn: heed::RoTxn<'e>,
+    databases: &'e [Database<Str, Str>],
+}
+
+impl StoreReader for HeedReader<'_> {
+    fn get(&self, table: Table, key: &str) -> anyhow::Result<Option<&str>> {
+        Ok(self.databases[table.index()].get(&self.rtxn, key)?)
+    }
+
+    fn len(&self, table: Table) -> anyhow::Result<usize> {
+        Ok(self.databases[table.index()].len(&self.rtxn)?)
+    }
+
+    fn iter(&self, table: Table) -> anyhow::Result<Entries<'_>> {
+        let iter = self.databases[table.index()].iter(&self.rtxn)?;
+        Ok(Box::new(iter.map(|result| result.map_err(Into::into))))
+    }
+}
+
+impl LookupStore for HeedStore {
+    fn read(&self, f: &mut dyn FnMut(&dyn StoreReader) -> anyhow::Result<()>) -> anyhow::Result<()> {
+        let inner = self.inner.read().map_err(|_| anyhow!("poisoned store lock"))?;
+        let inner = inner.as_ref().ok_or_else(|| anyhow!("closed store"))?;
+        let reader = HeedReader { rtxn: inner.env.read_txn()?, databases: &inner.databases };
+        f(&reader)
+    }
+
+    fn write(&self, entries: &[(Table, String, String)]) -> anyhow::Result<()> {
+        for _ in 0..=MAX_GROWTHS {
+            let result = {
+                let inner = self.inner.read().map_err(|_| anyhow!("poisoned store lock"))?;
+                let inner = inner.as_ref().ok_or_else(|| anyhow!("closed store"))?;
+                let mut wtxn = inner.env.write_txn()?;
+                entries.iter()
+                    .try_for_each(|(table, key, value)| inner.databases[table.index()].put(&mut wtxn, key, value))
+                    .and_then(|()| wtxn.commit())
+            };
+
+            match result {
+                Err(heed::Error::Mdb(MdbError::MapFull)) => self.grow()?,
+                result => return result.map_err(Into::into),
+            }
+        }
+        bail!("the entries don't fit in the LMDB map, even after growing it {} times", MAX_GROWTHS + 1)
+    }
+}
+
+/// A store entirely kept in memory, for machines with enough RAM.
+pub struct MemoryStore {
+    tables: RwLock<Vec<HashMap<String, String>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore { tables: RwLock::new(vec![HashMap::new(); Table::ALL.len()]) }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> MemoryStore {
+        MemoryStore::new()
+    }
+}
+
+struct MemoryReader<'s> {
+    tables: std::sync::RwLockReadGuard<'s, Vec<HashMap<String, String>>>,
+}
+
+impl StoreReader for MemoryReader<'_> {
+    fn get(&self, table: Table, key: &str) -> anyhow::Result<Option<&str>> {
+        Ok(self.tables[table.index()].get(key).map(String::as_str))
+    }
+
+    fn len(&self, table: Table) -> anyhow::Result<usize> {
+        Ok(self.tables[table.index()].len())
+    }
+
+    fn iter(&self, table: Table) -> anyhow::Result<Entries<'_>> {
+        let mut entries: Vec<_> = self.tables[table.index()].iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        entries.sort_unstable_by_key(|(key, _)| *key);
+        Ok(Box::new(entries.into_iter().map(Ok)))
+    }
+}
+
+impl LookupStore for MemoryStore {
+    fn read(&self, f: &mut dyn FnMut(&dyn StoreReader) -> anyhow::Result<()>) -> anyhow::Result<()> {
+        let tables = self.tables.read().map_err(|_| anyhow!("poisoned store lock"))?;
+        f(&MemoryReader { tables })
+    }
+
+    fn write(&self, entries: &[(Table, String, String)]) -> anyhow::Result<()> {
+        let mut tables = self.tables.write().map_err(|_| anyhow!("poisoned store lock"))?;
+        for (table, key, value) in entries {
+            tables[table.index()].insert(key.clone(), value.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn grow_a_small_map() {
+        let dir = tempfile::tempdir().unwrap();
+        let sizes = Arc::new(Mutex::new(Vec::new()));
+        let grown = sizes.clone();
+        // Too small for the databases and not a multiple of the memory pages.
+        let store = HeedStore::open(dir.path(), 1000).unwrap().on_grow(move |size| grown.lock().unwrap().push(size));
+
+        let value = "a".repeat(1024);
+        let entries: Vec<_> = (0..3000).map(|i| (Table::Editions, format!("/books/OL{}M", i), value.clone())).collect();
+        store.write(&entries).unwrap();
+        let sizes = sizes.lock().unwrap().clone();
+        assert!(!sizes.is_empty());
+        assert_eq!(sizes, (1..=sizes.len()).map(|i| MIN_MAP_SIZE << i).collect::<Vec<_>>());
+        drop(store);
+
+        // Reopened without a size, the map grows from the size of the store.
+        let store = HeedStore::open(dir.path(), 0).unwrap();
+        store.write(&entries).unwrap();
+        store.read(&mut |reader| {
+            assert_eq!(reader.len(Table::Editions)?, 3000);
+            Ok(())
+        }).unwrap();
+    }
+}
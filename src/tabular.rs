@@ -0,0 +1,216 @@
+//! Writes the books and the authors as flat CSV or TSV files.
+
+use std::io;
+
+use serde_json::Value;
+
+use crate::extract::DocumentType;
+use crate::object::OutObject;
+use crate::sink::{Encoder, Sink};
+
+/// The columns written when none are given.
+const BOOK_COLUMNS: &[&str] = &[
+    "id", "name", "subtitle", "work_id", "authors", "publish_year", "publish_date", "number_of_pages",
+    "publishers", "format", "physical_format", "subjects", "languages", "isbns",
+];
+const AUTHOR_COLUMNS: &[&str] = &[
+    "id", "name", "personal_name", "alternate_names", "birth_date", "birth_year", "death_date", "death_year", "bio",
+];
+
+/// The layout of the CSV files.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// The delimiter of the columns, e.g. `b'\t'` for TSV files.
+    pub delimiter: u8,
+    /// The columns in their order, the most common fields by default.
+    ///
+    /// A column is either a field of the documents, e.g. `subjects`, or an identifier scheme
+    /// of the books (e.g. `goodreads`) or of the authors (e.g. `wikidata`).
+    pub columns: Option<Vec<String>>,
+    /// The separator of the values of the lists, e.g. the `authors` or the `subjects`, joined into a single cell.
+    pub separator: String,
+    /// The list written as many rows, one for each of its values, e.g. `authors`.
+    /// It is ignored when it isn't one of the columns.
+    pub explode: Option<String>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions { delimiter: b',', columns: None, separator: "|".to_owned(), explode: None }
+    }
+}
+
+/// Writes the rows of the documents, on the worker threads.
+#[derive(Debug, Clone)]
+pub struct CsvEncoder {
+    kind: DocumentType,
+    columns: Vec<String>,
+    delimiter: u8,
+    separator: String,
+    /// The index of the exploded column.
+    explode: Option<usize>,
+}
+
+impl CsvEncoder {
+    /// The values of each column of the document.
+    fn cells(&self, object: &OutObject) -> anyhow::Result<Vec<Vec<String>>> {
+        let value = serde_json::to_value(object)?;
+        let fields = value.as_object().map(|fields| {
+            let identifiers = ["identifiers", "remote_ids"].iter().filter_map(|field| fields.get(*field)?.as_object());
+            (fields, identifiers.collect::<Vec<_>>())
+        });
+
+        let mut cells = Vec::with_capacity(self.columns.len());
+        for column in &self.columns {
+            let value = fields.as_ref().and_then(|(fields, identifiers)| {
+                fields.get(column).or_else(|| identifiers.iter().find_map(|identifiers| identifiers.get(column)))
+            });
+            cells.push(value.map_or_else(Vec::new, texts));
+        }
+        Ok(cells)
+    }
+}
+
+/// The texts of a value, many of them for a list.
+fn texts(value: &Value) -> Vec<String> {
+    match value {
+        Value::Null => Vec::new(),
+        Value::String(text) => vec![text.clone()],
+        Value::Array(values) => values.iter().flat_map(texts).collect(),
+        // The nested authors are flattened to their names.
+        Value::Object(fields) => match fields.get("name") {
+            Some(Value::String(name)) => vec![name.clone()],
+            _ => vec![value.to_string()],
+        },
+        Value::Bool(_) | Value::Number(_) => vec![value.to_string()],
+    }
+}
+
+impl Encoder for CsvEncoder {
+    type Batch = Vec<u8>;
+
+    fn encode(&self, buffer: &mut Vec<u8>, object: OutObject) -> anyhow::Result<()> {
+        if object.document_type() != self.kind {
+            anyhow::bail!("a {} can't be written with the {}s", object.document_type().name(), self.kind.name());
+        }
+
+        let mut cells = self.cells(&object)?;
+        // The values of the exploded list are written one per row, an empty list gives an empty cell.
+        let exploded = self.explode.map(|column| (column, std::mem::take(&mut cells[column])));
+        let mut row: Vec<_> = cells.iter().map(|values| values.join(&self.separator)).collect();
+
+        let mut writer = csv::WriterBuilder::new().delimiter(self.delimiter).buffer_capacity(1024).from_writer(buffer);
+        match exploded {
+            Some((column, values)) if !values.is_empty() => {
+                for value in values {
+                    row[column] = value;
+                    writer.write_record(&row)?;
+                }
+            },
+            _ => writer.write_record(&row)?,
+        }
+        writer.flush().map_err(Into::into)
+    }
+}
+
+/// Writes the books or the authors as a CSV file, starting with the names of the columns.
+pub struct CsvSink<W> {
+    writer: W,
+    encoder: CsvEncoder,
+}
+
+impl<W: io::Write> CsvSink<W> {
+    pub fn new(mut writer: W, kind: DocumentType, options: CsvOptions) -> anyhow::Result<CsvSink<W>> {
+        let columns = match (options.columns, kind) {
+            (Some(columns), _) => columns,
+            (None, DocumentType::Book) => BOOK_COLUMNS.iter().map(|c| c.to_string()).collect(),
+            (None, DocumentType::Author) => AUTHOR_COLUMNS.iter().map(|c| c.to_string()).collect(),
+            (None, DocumentType::Work) => anyhow::bail!("the works can't be written as CSV"),
+        };
+
+        let mut header = csv::WriterBuilder::new().delimiter(options.delimiter).from_writer(&mut writer);
+        header.write_record(&columns)?;
+        header.flush()?;
+        drop(header);
+
+        let explode = options.explode.and_then(|explode| columns.iter().position(|column| *column == explode));
+        let encoder = CsvEncoder { kind, columns, delimiter: options.delimiter, separator: options.separator, explode };
+        Ok(CsvSink { writer, encoder })
+    }
+
+    /// The names of the columns, in their order.
+    pub fn columns(&self) -> &[String] {
+        &self.encoder.columns
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: io::Write> Sink for CsvSink<W> {
+    type Encoder = CsvEncoder;
+
+    fn encoder(&self) -> CsvEncoder {
+        self.encoder.clone()
+    }
+
+    fn write(&mut self, batch: Vec<u8>) -> anyhow::Result<()> {
+        self.writer.write_all(&batch).map_err(Into::into)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.writer.flush().map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::Options;
+    use crate::testing::extract_sample;
+
+    fn books(options: CsvOptions) -> Vec<csv::StringRecord> {
+        let mut sink = CsvSink::new(Vec::new(), DocumentType::Book, options).unwrap();
+        extract_sample(Options { types: vec![DocumentType::Book], ..Options::default() }, &mut sink).unwrap();
+
+        let output = sink.into_inner();
+        let mut reader = csv::ReaderBuilder::new().has_headers(false).delimiter(b'\t').from_reader(output.as_slice());
+        reader.records().map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn join_the_arrays() {
+        let columns = ["id", "isbns", "goodreads", "publish_year"].iter().map(|c| c.to_string()).collect();
+        let options = CsvOptions { delimiter: b'\t', columns: Some(columns), separator: "; ".to_owned(), explode: None };
+        let records = books(options);
+
+        assert_eq!(records.len(), 101);
+        assert_eq!(&records[0], vec!["id", "isbns", "goodreads", "publish_year"]);
+        let book = records.iter().find(|record| &record[0] == "OL10000135M").unwrap();
+        assert_eq!(book, vec!["OL10000135M", "9780107805401", "6850240", "1993"]);
+    }
+
+    #[test]
+    fn explode_a_single_array() {
+        let columns: Vec<_> = ["id", "isbns", "publishers"].iter().map(|c| c.to_string()).collect();
+        let options = CsvOptions { delimiter: b'\t', columns: Some(columns.clone()), ..CsvOptions::default() };
+        let joined = books(options);
+        let explode = Some("publishers".to_owned());
+        let exploded = books(CsvOptions { delimiter: b'\t', columns: Some(columns), explode, ..CsvOptions::default() });
+
+        // A row for each publisher of each book, the other lists stay joined.
+        let count = |record: &csv::StringRecord| record[2].split('|').filter(|value| !value.is_empty()).count().max(1);
+        assert_eq!(exploded.len() - 1, joined[1..].iter().map(count).sum::<usize>());
+        assert!(joined.iter().any(|record| count(record) > 1));
+        for record in &joined[1..] {
+            let rows: Vec<_> = exploded.iter().filter(|row| row[0] == record[0]).collect();
+            assert_eq!(rows.len(), count(record));
+            for row in rows {
+                assert_eq!(&row[1], &record[1]);
+                assert!(!row[2].contains('|'));
+                assert!(record[2].split('|').any(|publisher| publisher == &row[2]));
+            }
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! The fixtures shared by the tests.
+
+use crate::extract::{Extractor, Options, Summary};
+use crate::sink::Sink;
+use crate::store::MemoryStore;
+
+/// The first records of a dump: 100 editions, 97 authors and a few redirects.
+pub const SAMPLE: &str = include_str!("../sample_dataset.txt");
+
+/// Extracts the documents of the dump into the sink, with an in-memory store.
+pub fn extract_dump<S: Sink>(dump: &[u8], options: Options, sink: &mut S) -> anyhow::Result<Summary> {
+    let store = MemoryStore::new();
+    Extractor::new(&store, options).threads(2).extract(dump, |_| Ok(()), sink)
+}
+
+/// Extracts the documents of the sample dump into the sink.
+pub fn extract_sample<S: Sink>(options: Options, sink: &mut S) -> anyhow::Result<Summary> {
+    extract_dump(SAMPLE.as_bytes(), options, sink)
+}